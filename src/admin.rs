@@ -0,0 +1,138 @@
+use anyhow::Result;
+use libirc::client::Sender;
+
+use crate::state::State;
+
+/// Prefix of admin commands on both sides.
+pub const PREFIX: &str = "!admin";
+
+const USAGE: &str = "Commands: status, pause, resume, ignores, ignore <irc|discord> <name>, \
+                     unignore <irc|discord> <name>, rules, member-changes <on|off>, reconnect";
+
+/// Returns the arguments of an admin command, if `line` is one.
+pub fn parse(line: &str) -> Option<&str> {
+    let args = line.trim().strip_prefix(PREFIX)?;
+    (args.is_empty() || args.starts_with(' ')).then(|| args.trim())
+}
+
+/// Runs an admin command of `admin`, returning the answer. Setting changes are saved to the
+/// state file.
+pub fn run(state: &State, irc_sender: &Sender, admin: &str, args: &str) -> Result<String> {
+    let mut words = args.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let side = words.next();
+    let name = words.next();
+    info!("{} ran admin command: {}", admin, args);
+
+    let answer = match (command, side, name) {
+        ("status", _, _) => {
+            let settings = state.settings().clone();
+            let (nickname, joined) = {
+                let session = state.irc();
+                (session.nickname.clone(), session.joined)
+            };
+            format!(
+                "Relaying {}. IRC: {}, {}. Member changes {}. Ignoring {} IRC and {} Discord users.",
+                if settings.paused { "paused" } else { "active" },
+                nickname.as_deref().unwrap_or("not connected"),
+                if joined {
+                    "in the channel"
+                } else {
+                    "not in the channel"
+                },
+                if settings.bridge_member_changes {
+                    "bridged"
+                } else {
+                    "not bridged"
+                },
+                settings.irc_ignores.len(),
+                settings.discord_ignores.len(),
+            )
+        }
+        ("pause", _, _) => {
+            state.update_settings(|settings| settings.paused = true)?;
+            "Relaying is paused.".to_string()
+        }
+        ("resume", _, _) => {
+            state.update_settings(|settings| settings.paused = false)?;
+            "Relaying is resumed.".to_string()
+        }
+        ("ignores", _, _) => {
+            let settings = state.settings();
+            format!(
+                "Ignored on IRC: {}. Ignored on Discord: {}.",
+                list(&settings.irc_ignores),
+                list(&settings.discord_ignores)
+            )
+        }
+        ("rules", _, _) => {
+            let hits = state.ignore_rules.hits();
+            if hits.is_empty() {
+                "No ignore rules.".to_string()
+            } else {
+                let hits: Vec<_> = hits
+                    .iter()
+                    .map(|(rule, count)| format!("{} ({})", rule, count))
+                    .collect();
+                format!("Messages ignored by rule: {}.", hits.join(", "))
+            }
+        }
+        ("ignore" | "unignore", Some(side @ ("irc" | "discord")), Some(name)) => {
+            let ignore = command == "ignore";
+            let casemapping = state.irc().casemapping;
+            state.update_settings(|settings| {
+                let ignores = if side == "irc" {
+                    // IRC nicknames are compared with the casemapping of the server.
+                    settings
+                        .irc_ignores
+                        .retain(|ignored| !casemapping.eq(ignored, name));
+                    &mut settings.irc_ignores
+                } else {
+                    settings.discord_ignores.retain(|ignored| ignored != name);
+                    &mut settings.discord_ignores
+                };
+                if ignore {
+                    ignores.push(name.to_string());
+                }
+            })?;
+            format!(
+                "{} is {} on {}.",
+                name,
+                if ignore { "ignored" } else { "not ignored" },
+                side
+            )
+        }
+        ("member-changes", Some(toggle @ ("on" | "off")), _) => {
+            let bridge = toggle == "on";
+            state.update_settings(|settings| settings.bridge_member_changes = bridge)?;
+            format!(
+                "Member changes are {}.",
+                if bridge { "bridged" } else { "not bridged" }
+            )
+        }
+        ("reconnect", _, _) => {
+            // The bridge exits when the connection closes, to be restarted by its supervisor.
+            irc_sender.send_quit(format!("Reconnecting, asked by {}", admin))?;
+            "Reconnecting to IRC.".to_string()
+        }
+        _ => USAGE.to_string(),
+    };
+    Ok(answer)
+}
+
+fn list(names: &[String]) -> String {
+    if names.is_empty() {
+        "nobody".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse("!admin pause"), Some("pause"));
+    assert_eq!(parse(" !admin  ignore irc bob "), Some("ignore irc bob"));
+    assert_eq!(parse("!admin"), Some(""));
+    assert_eq!(parse("!administrator"), None);
+    assert_eq!(parse("hello"), None);
+}
@@ -0,0 +1,62 @@
+/// How the IRC server compares nicknames, from the `CASEMAPPING` token of RPL_ISUPPORT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMapping {
+    /// Only `A-Z` are the uppercase of `a-z`.
+    Ascii,
+    /// `[]\~` are also the uppercase of `{}|^`. Servers which do not tell use this.
+    #[default]
+    Rfc1459,
+    /// `[]\` are also the uppercase of `{}|`.
+    Rfc1459Strict,
+}
+
+impl CaseMapping {
+    /// Reads a `CASEMAPPING` token. Other mappings, like `rfc7613`, are compared as ASCII.
+    pub fn parse(token: &str) -> Self {
+        match token {
+            "rfc1459" => CaseMapping::Rfc1459,
+            "rfc1459-strict" => CaseMapping::Rfc1459Strict,
+            _ => CaseMapping::Ascii,
+        }
+    }
+
+    fn lower_char(self, c: char) -> char {
+        match (self, c) {
+            (_, 'A'..='Z') => c.to_ascii_lowercase(),
+            (CaseMapping::Rfc1459 | CaseMapping::Rfc1459Strict, '[') => '{',
+            (CaseMapping::Rfc1459 | CaseMapping::Rfc1459Strict, ']') => '}',
+            (CaseMapping::Rfc1459 | CaseMapping::Rfc1459Strict, '\\') => '|',
+            (CaseMapping::Rfc1459, '~') => '^',
+            _ => c,
+        }
+    }
+
+    /// Returns the lowercase form of a nickname, equal for all the ways to write it.
+    pub fn lower(self, nickname: &str) -> String {
+        nickname.chars().map(|c| self.lower_char(c)).collect()
+    }
+
+    /// Returns whether two nicknames are the same for the server.
+    pub fn eq(self, a: &str, b: &str) -> bool {
+        a.len() == b.len()
+            && a.chars()
+                .zip(b.chars())
+                .all(|(a, b)| self.lower_char(a) == self.lower_char(b))
+    }
+}
+
+#[test]
+fn test_casemapping() {
+    assert!(CaseMapping::Ascii.eq("GitHub", "github"));
+    assert!(!CaseMapping::Ascii.eq("foo[m]", "foo{m}"));
+    assert!(CaseMapping::Rfc1459.eq("Foo[m]\\~", "foo{m}|^"));
+    assert!(CaseMapping::Rfc1459Strict.eq("Foo[m]\\", "foo{m}|"));
+    assert!(!CaseMapping::Rfc1459Strict.eq("foo~", "foo^"));
+    assert!(!CaseMapping::Rfc1459.eq("foo", "foobar"));
+    assert_eq!(CaseMapping::Rfc1459.lower("[Bot]"), "{bot}");
+    assert_eq!(
+        CaseMapping::parse("rfc1459-strict"),
+        CaseMapping::Rfc1459Strict
+    );
+    assert_eq!(CaseMapping::parse("rfc7613"), CaseMapping::Ascii);
+}
@@ -10,26 +10,438 @@ pub struct IrcOzingerConfig {
     pub password: String,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum SaslMechanism {
+    /// Authenticate with an account name and a password.
+    #[default]
+    #[serde(rename = "PLAIN", alias = "plain")]
+    Plain,
+    /// Authenticate with the TLS client certificate set by `client_cert_path`.
+    #[serde(rename = "EXTERNAL", alias = "external")]
+    External,
+}
+
+impl SaslMechanism {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::External => "EXTERNAL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IrcSaslConfig {
+    #[serde(default)]
+    pub mechanism: SaslMechanism,
+    /// Account name for the `PLAIN` mechanism. Defaults to the nickname of the bot.
+    pub username: Option<String>,
+    /// Account password for the `PLAIN` mechanism.
+    #[serde(default)]
+    pub password: String,
+}
+
+/// How to take the configured nickname back when someone else is using it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NickRecovery {
+    /// Keep using the alternative nickname.
+    None,
+    /// Disconnect the other user with `GHOST`, then change the nickname once it is free.
+    Ghost,
+    /// Rename the other user with `RECOVER`, then change the nickname once it is free.
+    Recover,
+    /// Let the services change the nickname with `REGAIN`.
+    #[default]
+    Regain,
+}
+
+impl NickRecovery {
+    pub fn command(&self) -> Option<&'static str> {
+        match self {
+            NickRecovery::None => None,
+            NickRecovery::Ghost => Some("GHOST"),
+            NickRecovery::Recover => Some("RECOVER"),
+            NickRecovery::Regain => Some("REGAIN"),
+        }
+    }
+}
+
+/// Identification to the nickname service. It replaces `nick_password`, `should_ghost` and
+/// `ghost_sequence` of the IRC library, which send the `NICKSERV` alias that not every network
+/// has, cannot identify to an account named differently from the nickname, and change the
+/// nickname before the services freed it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IrcServicesConfig {
+    /// Nickname of the nickname service.
+    #[serde(default = "default_nickserv")]
+    pub nickserv: String,
+    /// Account name to identify as. Defaults to the nickname of the bot.
+    pub account: Option<String>,
+    pub password: String,
+    #[serde(default)]
+    pub recovery: NickRecovery,
+    /// By setting this option as `true`, the bot joins the channel only after it has been
+    /// identified, or after `identify_timeout` seconds have passed.
+    #[serde(default)]
+    pub wait_for_identification: bool,
+    #[serde(default = "default_identify_timeout")]
+    pub identify_timeout: u64,
+}
+
+fn default_nickserv() -> String {
+    "NickServ".to_string()
+}
+
+fn default_identify_timeout() -> u64 {
+    10
+}
+
+/// Reports need the `echo-message` capability, to tell which line the server refused.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeliveryReportsConfig {
+    /// Reaction added to Discord messages which IRC refused to deliver.
+    #[serde(default = "default_failure_reaction")]
+    pub failure_reaction: String,
+    /// Reaction added to Discord messages which IRC delivered.
+    pub success_reaction: Option<String>,
+    /// By setting this option as `true`, the bot also replies to refused messages with the reason,
+    /// deleting the reply after `explanation_lifetime` seconds.
+    #[serde(default)]
+    pub explain: bool,
+    #[serde(default = "default_explanation_lifetime")]
+    pub explanation_lifetime: u64,
+}
+
+fn default_failure_reaction() -> String {
+    "❌".to_string()
+}
+
+fn default_explanation_lifetime() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorrectionsConfig {
+    /// Corrections of messages older than this number of seconds are posted as new messages.
+    #[serde(default = "default_correction_window")]
+    pub window: u64,
+}
+
+fn default_correction_window() -> u64 {
+    300
+}
+
+fn default_rejoin_delay() -> u64 {
+    5
+}
+
+fn default_max_rejoin_delay() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivilegeDisplay {
+    #[default]
+    Hidden,
+    /// Like `@alice`.
+    Prefix,
+    /// Like `alice (@)`.
+    Suffix,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModesConfig {
+    /// Letters of the channel modes whose changes are announced on Discord.
+    #[serde(default = "default_announced_modes")]
+    pub announce: String,
+    /// How the privilege of IRC users in the channel is shown in their Discord name.
+    #[serde(default)]
+    pub privilege: PrivilegeDisplay,
+}
+
+fn default_announced_modes() -> String {
+    "qaohvb".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IrcAdminsConfig {
+    /// Masks like `*!*@trusted.example.com` of the IRC users allowed to use admin commands.
+    #[serde(default)]
+    pub hostmasks: Vec<String>,
+    /// Services accounts allowed to use admin commands. Requires the `account-tag` capability.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct IrcConfig {
     #[serde(flatten)]
     pub connection: IrcConnectionConfig,
     pub channel: String,
+    /// Key (`+k` password) of the channel.
+    pub channel_key: Option<String>,
+    /// By setting this option as `true`, the bot joins the channel when it is invited to it, which
+    /// is required for invite only (`+i`) channels.
+    #[serde(default)]
+    pub join_on_invite: bool,
+    /// By setting this option as `true`, the bot tries to join the channel again after it has been
+    /// kicked or refused, waiting `rejoin_delay` seconds at first and doubling the delay up to
+    /// `max_rejoin_delay` seconds on repeated failures.
+    #[serde(default)]
+    pub auto_rejoin: bool,
+    #[serde(default = "default_rejoin_delay")]
+    pub rejoin_delay: u64,
+    #[serde(default = "default_max_rejoin_delay")]
+    pub max_rejoin_delay: u64,
     #[serde(default)]
     pub ignores: Vec<String>,
     pub ozinger: Option<IrcOzingerConfig>,
+    /// Authenticate with SASL during the capability negotiation. The bot refuses to join the
+    /// channel when the authentication fails.
+    pub sasl: Option<IrcSaslConfig>,
+    /// Identify to the nickname service, and take the configured nickname back when it is in use.
+    pub services: Option<IrcServicesConfig>,
     #[serde(default)]
     pub bridge_member_changes: bool,
     /// By setting this option as `true`, you can keep the bot from notifying people with nicknames
     /// by inserting zero width spaces (U+200B) into nicknames.
     #[serde(default)]
     pub prevent_noti_by_nicknames: bool,
+    /// Apply `s/old/new/` corrections of IRC users to their last message by editing it on
+    /// Discord, instead of posting them.
+    pub corrections: Option<CorrectionsConfig>,
+    /// Report Discord messages which the IRC server refused to deliver, e.g. because the channel
+    /// is moderated.
+    pub delivery_reports: Option<DeliveryReportsConfig>,
+    /// Announce channel mode changes on Discord and show the privileges of IRC users.
+    pub modes: Option<ModesConfig>,
+    /// Let these IRC users manage the bridge with `!admin` commands.
+    pub admins: Option<IrcAdminsConfig>,
     /// By setting this option as `true`, this bot will automatically detect the avatar of IRC
     /// users by searching for the user with the same nickname on the Discord channel.
     #[serde(default)]
     pub auto_detect_avatar: bool,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrivateMessagesConfig {
+    /// Discord channel private messages to the bot are relayed to.
+    pub channel_id: u64,
+    /// By setting this option as `true`, private messages of every IRC user are relayed into their
+    /// own private thread of the channel, and every message in the thread is sent back to the
+    /// user. Otherwise, Discord users answer by replying to a relayed message.
+    #[serde(default)]
+    pub use_threads: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditFormat {
+    /// Send a `s/old/new/` substitution, or the whole message if the change cannot be described
+    /// that way.
+    #[default]
+    Diff,
+    /// Send the whole edited message.
+    Full,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EditsConfig {
+    /// Edits made more than this number of seconds after the message was sent are not relayed.
+    #[serde(default = "default_edit_window")]
+    pub window: u64,
+    #[serde(default)]
+    pub format: EditFormat,
+}
+
+fn default_edit_window() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeletionsConfig {
+    /// Deletions made more than this number of seconds after the message was sent are not
+    /// announced.
+    #[serde(default = "default_deletion_window")]
+    pub window: u64,
+}
+
+fn default_deletion_window() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReactionsConfig {
+    /// Reactions to a message made within this number of seconds are announced in a single line.
+    #[serde(default = "default_reaction_throttle")]
+    pub throttle: u64,
+    /// Send IRCv3 reactions instead, when the server allows client tags.
+    #[serde(default = "default_true")]
+    pub use_tags: bool,
+}
+
+fn default_reaction_throttle() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TypingConfig {
+    /// Minimum number of seconds between two typing notifications sent to IRC.
+    #[serde(default = "default_irc_typing_interval")]
+    pub irc_interval: u64,
+    /// Minimum number of seconds between two typing indicators shown on Discord.
+    #[serde(default = "default_discord_typing_interval")]
+    pub discord_interval: u64,
+}
+
+fn default_irc_typing_interval() -> u64 {
+    3
+}
+
+fn default_discord_typing_interval() -> u64 {
+    8
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadMode {
+    /// Relay thread messages to the bridged IRC channel, prefixed with `[thread name]`.
+    #[default]
+    Prefix,
+    /// Relay every thread to its own IRC channel, named after `channel_name`.
+    Channels,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreadsConfig {
+    #[serde(default)]
+    pub mode: ThreadMode,
+    /// Name of the IRC channel of a thread, where `{channel}` is the bridged IRC channel and
+    /// `{thread}` the name of the thread.
+    #[serde(default = "default_thread_channel_name")]
+    pub channel_name: String,
+}
+
+fn default_thread_channel_name() -> String {
+    "{channel}-{thread}".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopicDirection {
+    IrcToDiscord,
+    DiscordToIrc,
+    #[default]
+    Both,
+}
+
+impl TopicDirection {
+    pub fn to_discord(self) -> bool {
+        self != TopicDirection::DiscordToIrc
+    }
+
+    pub fn to_irc(self) -> bool {
+        self != TopicDirection::IrcToDiscord
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopicConfig {
+    #[serde(default)]
+    pub direction: TopicDirection,
+    /// Announce topic changes on the other side.
+    #[serde(default = "default_true")]
+    pub announce: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamesConfig {
+    /// Number of IRC users listed on a page.
+    #[serde(default = "default_names_page_size")]
+    pub page_size: usize,
+}
+
+impl Default for NamesConfig {
+    fn default() -> Self {
+        NamesConfig {
+            page_size: default_names_page_size(),
+        }
+    }
+}
+
+fn default_names_page_size() -> usize {
+    50
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordAdminsConfig {
+    /// IDs of the Discord users allowed to use admin commands.
+    #[serde(default)]
+    pub users: Vec<u64>,
+    /// IDs of the Discord roles whose members are allowed to use admin commands.
+    #[serde(default)]
+    pub roles: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandsConfig {
+    /// Number of seconds to wait for the IRC server to answer `/irc whois`.
+    #[serde(default = "default_command_timeout")]
+    pub timeout: u64,
+}
+
+fn default_command_timeout() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForumConfig {
+    /// Discord forum channel to bridge.
+    pub channel_id: u64,
+    /// IRC channel where the posts of the forum are relayed.
+    pub irc_channel: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BotsConfig {
+    /// IDs of the bot users whose messages are relayed.
+    #[serde(default)]
+    pub users: Vec<u64>,
+    /// IDs of the webhooks whose messages are relayed. The webhook of the bridge is never relayed.
+    #[serde(default)]
+    pub webhooks: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayPermissionsConfig {
+    /// IDs of the roles whose members are relayed. Everyone is relayed if empty.
+    #[serde(default)]
+    pub allowed_roles: Vec<u64>,
+    /// IDs of the roles whose members are not relayed, even with an allowed role.
+    #[serde(default)]
+    pub blocked_roles: Vec<u64>,
+    /// Minimum age of the Discord account in seconds.
+    #[serde(default)]
+    pub min_account_age: u64,
+    /// Minimum number of seconds since the user joined the server.
+    #[serde(default)]
+    pub min_member_age: u64,
+    /// Notice sent privately to users whose messages are not relayed, where `{reason}` is why.
+    /// Empty to send none.
+    #[serde(default = "default_refusal_notice")]
+    pub notice: String,
+}
+
+fn default_refusal_notice() -> String {
+    "Sorry, your messages are not relayed to IRC because {reason}.".to_string()
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct DiscordConfig {
     pub token: String,
@@ -38,12 +450,136 @@ pub struct DiscordConfig {
     pub webhook_token: String,
     #[serde(default)]
     pub ignores: Vec<String>,
+    /// Relay private messages to the IRC bot into a separate channel, instead of dropping them.
+    pub private_messages: Option<PrivateMessagesConfig>,
+    /// Relay edits of Discord messages to IRC as corrections.
+    pub edits: Option<EditsConfig>,
+    /// Announce deletions of relayed Discord messages on IRC.
+    pub deletions: Option<DeletionsConfig>,
+    /// Relay reactions to bridged messages to IRC.
+    pub reactions: Option<ReactionsConfig>,
+    /// Show on each side when someone is typing on the other one.
+    pub typing: Option<TypingConfig>,
+    /// Relay threads of the bridged channel, instead of dropping their messages.
+    pub threads: Option<ThreadsConfig>,
+    /// Bridge a forum channel to another IRC channel.
+    pub forum: Option<ForumConfig>,
+    /// Synchronize the topics of the IRC channel and the Discord channel.
+    pub topic: Option<TopicConfig>,
+    /// Let users list the members of the other side with `!names`, or `/irc-names` on Discord.
+    pub names: Option<NamesConfig>,
+    /// Register the `/irc` command, to look at the IRC side from Discord.
+    pub commands: Option<CommandsConfig>,
+    /// Let these Discord users manage the bridge with `!admin` commands.
+    pub admins: Option<DiscordAdminsConfig>,
+    /// Only relay the Discord members meeting these conditions.
+    pub relay_permissions: Option<RelayPermissionsConfig>,
+    /// Relay the messages of these bots and webhooks, which are dropped otherwise.
+    pub bots: Option<BotsConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoreConfig {
+    /// Path of the SQLite database of bridged messages. Messages are only kept in memory if
+    /// omitted.
+    pub path: Option<PathBuf>,
+    /// Bridged messages are kept for this number of seconds.
+    #[serde(default = "default_retention")]
+    pub retention: u64,
+    /// Path of the TOML file where the settings changed with admin commands are saved. Changes are
+    /// lost on restart if omitted.
+    pub state_file: Option<PathBuf>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            path: None,
+            retention: default_retention(),
+            state_file: None,
+        }
+    }
+}
+
+fn default_retention() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnoreDirection {
+    #[default]
+    Both,
+    IrcToDiscord,
+    DiscordToIrc,
+}
+
+/// Messages matching every condition set in a rule are not relayed. Conditions about one side only
+/// never match messages from the other side.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IgnoreRuleConfig {
+    /// Name of the rule in logs and hit counts.
+    pub name: String,
+    #[serde(default)]
+    pub direction: IgnoreDirection,
+    /// IRC mask like `*!*@bots.example.org`.
+    pub hostmask: Option<String>,
+    /// IRC services account. Requires the `account-tag` capability.
+    pub account: Option<String>,
+    /// IRC nickname or Discord username.
+    pub nickname: Option<String>,
+    /// Discord user ID.
+    pub user_id: Option<u64>,
+    /// Discord role ID.
+    pub role_id: Option<u64>,
+    /// Regular expression searched in the message.
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoopDetectionConfig {
+    /// Number of seconds relayed messages are remembered to recognize their echoes.
+    #[serde(default = "default_loop_window")]
+    pub window: u64,
+    /// Other bridges relaying the IRC channel or the Discord channel elsewhere.
+    #[serde(default)]
+    pub bridges: Vec<BridgeConfig>,
+}
+
+fn default_loop_window() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeConfig {
+    /// Name of the bridge in logs.
+    pub name: String,
+    /// Nicknames of the bridge on IRC.
+    #[serde(default)]
+    pub irc_nicknames: Vec<String>,
+    /// IDs of the bot users or webhooks of the bridge on Discord.
+    #[serde(default)]
+    pub discord_ids: Vec<u64>,
+    /// Regular expression of the lines the bridge relays, capturing the original message in a
+    /// `text` group.
+    #[serde(default = "default_relay_pattern")]
+    pub pattern: String,
+}
+
+pub fn default_relay_pattern() -> String {
+    "^<[^>]+> (?P<text>.+)$".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub exit_on_send_error: bool,
+    #[serde(default)]
+    pub store: StoreConfig,
+    #[serde(default)]
+    pub ignore_rules: Vec<IgnoreRuleConfig>,
+    /// Drop the echoes of relayed messages coming back through other bridges.
+    pub loop_detection: Option<LoopDetectionConfig>,
     pub irc: IrcConfig,
     pub discord: DiscordConfig,
 }
@@ -0,0 +1,337 @@
+use std::time::Duration;
+
+use libirc::client::prelude::{ChannelExt, Command as IrcCommand};
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::channel::Channel;
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+
+use super::DiscordHandler;
+use super::names::{NAMES_COMMAND, render_names};
+
+pub(super) const IRC_COMMAND: &str = "irc";
+
+impl DiscordHandler {
+    /// Registers the configured commands in the guild of the bridged channel.
+    pub(super) async fn register_commands(&self, ctx: &Context) {
+        if self.config.names.is_none() && self.config.commands.is_none() {
+            return;
+        }
+        let guild_id = match ChannelId(self.config.channel_id).to_channel(ctx).await {
+            Ok(Channel::Guild(channel)) => channel.guild_id,
+            Ok(_) => return,
+            Err(e) => {
+                warn!("Failed to get the bridged channel: {}", e);
+                return;
+            }
+        };
+
+        if self.config.names.is_some() {
+            let result = guild_id
+                .create_application_command(&ctx.http, |command| {
+                    command
+                        .name(NAMES_COMMAND)
+                        .description("List the users in the IRC channel")
+                        .create_option(|option| {
+                            option
+                                .name("page")
+                                .description("Page of the list")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(1)
+                        })
+                })
+                .await;
+            if let Err(e) = result {
+                warn!("Failed to register /{}: {}", NAMES_COMMAND, e);
+            }
+        }
+
+        if self.config.commands.is_some() {
+            let result = guild_id
+                .create_application_command(&ctx.http, |command| {
+                    command
+                        .name(IRC_COMMAND)
+                        .description("Look at the IRC side of the bridge")
+                        .create_option(|option| {
+                            option
+                                .name("whois")
+                                .description("Show who an IRC user is")
+                                .kind(CommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("nick")
+                                        .description("Nickname of the user")
+                                        .kind(CommandOptionType::String)
+                                        .required(true)
+                                })
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("names")
+                                .description("List the users in the IRC channel")
+                                .kind(CommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("page")
+                                        .description("Page of the list")
+                                        .kind(CommandOptionType::Integer)
+                                        .min_int_value(1)
+                                })
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("topic")
+                                .description("Show the topic of the IRC channel")
+                                .kind(CommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("status")
+                                .description("Show the state of the IRC connection")
+                                .kind(CommandOptionType::SubCommand)
+                        })
+                        .create_option(|option| {
+                            option
+                                .name("msg")
+                                .description("Send a private message to an IRC user")
+                                .kind(CommandOptionType::SubCommand)
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("nick")
+                                        .description("Nickname of the user")
+                                        .kind(CommandOptionType::String)
+                                        .required(true)
+                                })
+                                .create_sub_option(|option| {
+                                    option
+                                        .name("text")
+                                        .description("Message to send")
+                                        .kind(CommandOptionType::String)
+                                        .required(true)
+                                })
+                        })
+                })
+                .await;
+            if let Err(e) = result {
+                warn!("Failed to register /{}: {}", IRC_COMMAND, e);
+            }
+        }
+    }
+
+    /// Answers the subcommands of `/irc`, only visible to the user.
+    pub(super) async fn answer_irc_command(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) {
+        let Some(config) = &self.config.commands else {
+            return;
+        };
+        let Some(subcommand) = command.data.options.first() else {
+            return;
+        };
+        let options = &subcommand.options;
+        let content = match subcommand.name.as_str() {
+            "whois" => {
+                let Some(nickname) = string_option(options, "nick") else {
+                    return;
+                };
+                // WHOIS replies may take longer than Discord waits for an answer.
+                if let Err(e) = command
+                    .create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                            .interaction_response_data(|data| data.ephemeral(true))
+                    })
+                    .await
+                {
+                    warn!("Failed to answer /{}: {}", IRC_COMMAND, e);
+                    return;
+                }
+                let content = self
+                    .whois(nickname, Duration::from_secs(config.timeout))
+                    .await;
+                if let Err(e) = command
+                    .edit_original_interaction_response(&ctx.http, |response| {
+                        response.content(content)
+                    })
+                    .await
+                {
+                    warn!("Failed to answer /{}: {}", IRC_COMMAND, e);
+                }
+                return;
+            }
+            "names" => {
+                let page = options
+                    .iter()
+                    .find(|option| option.name == "page")
+                    .and_then(|option| option.value.as_ref())
+                    .and_then(|value| value.as_u64())
+                    .unwrap_or(1) as usize;
+                let page_size = self.config.names.clone().unwrap_or_default().page_size;
+                render_names(
+                    &self.state.roster(),
+                    &self.irc_config.channel,
+                    page,
+                    page_size,
+                )
+            }
+            "topic" => match self.state.topics().irc.as_deref() {
+                Some("") => format!("**{}** has no topic.", self.irc_config.channel),
+                Some(topic) => format!("Topic of **{}**: {}", self.irc_config.channel, topic),
+                None => format!("The topic of **{}** is unknown.", self.irc_config.channel),
+            },
+            "status" => self.status(),
+            "msg" => {
+                let (Some(nickname), Some(text)) = (
+                    string_option(options, "nick"),
+                    string_option(options, "text"),
+                ) else {
+                    return;
+                };
+                let name = match &command.member {
+                    Some(member) => member.display_name().into_owned(),
+                    None => command.user.name.clone(),
+                };
+                if let Some(refusal) = self.msg_refusal(command, nickname, &name, text) {
+                    refusal
+                } else {
+                    info!("DIS> [PM to {}] <{}> {}", nickname, name, text);
+                    match self
+                        .irc_sender
+                        .send_privmsg(nickname, format!("<{}> {}", name, text))
+                    {
+                        Ok(()) => format!("Sent to **{}**.", nickname),
+                        Err(e) => format!("Failed to send the message: {}", e),
+                    }
+                }
+            }
+            _ => return,
+        };
+
+        let result = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|data| data.content(content).ephemeral(true))
+            })
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to answer /{}: {}", IRC_COMMAND, e);
+        }
+    }
+
+    async fn whois(&self, nickname: &str, timeout: Duration) -> String {
+        let receiver = self.state.start_whois(nickname);
+        if let Err(e) = self
+            .irc_sender
+            .send(IrcCommand::WHOIS(None, nickname.to_string()))
+        {
+            self.state.finish_whois(nickname);
+            return format!("Failed to send WHOIS: {}", e);
+        }
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(lines)) if !lines.is_empty() => lines.join("\n"),
+            Ok(_) => format!("The server told nothing about **{}**.", nickname),
+            Err(_) => {
+                self.state.finish_whois(nickname);
+                format!("The server did not answer about **{}**.", nickname)
+            }
+        }
+    }
+
+    /// Returns why `/irc msg` may not send `text` to `nickname`, with the checks of relayed
+    /// messages. Only the users of the bridged channel can be messaged, not channels or services.
+    fn msg_refusal(
+        &self,
+        command: &ApplicationCommandInteraction,
+        nickname: &str,
+        name: &str,
+        text: &str,
+    ) -> Option<String> {
+        if self.state.settings().paused {
+            return Some("Relaying is paused.".to_string());
+        }
+        if nickname.is_channel_name() || !self.state.roster().contains(nickname) {
+            return Some(format!(
+                "**{}** is not a user of **{}**.",
+                nickname, self.irc_config.channel
+            ));
+        }
+        let role_ids: Vec<_> = command
+            .member
+            .iter()
+            .flat_map(|member| member.roles.iter().map(|role| role.0))
+            .collect();
+        if self
+            .state
+            .settings()
+            .discord_ignores
+            .iter()
+            .any(|n| n == name)
+        {
+            debug!("DIS| [PM to {}] <{}(ignored)> {}", nickname, name, text);
+            return Some("Your messages are not relayed to IRC.".to_string());
+        }
+        if let Some(rule) = self.ignore_rule(&command.user, &role_ids, text) {
+            debug!(
+                "DIS| [PM to {}] Message of {} matched the ignore rule {}",
+                nickname, name, rule
+            );
+            return Some("Your messages are not relayed to IRC.".to_string());
+        }
+        let joined_at = command.member.as_ref().and_then(|member| member.joined_at);
+        let reason = self.refusal_of(&command.user, &role_ids, joined_at)?;
+        debug!(
+            "DIS| [PM to {}] <{}(refused: {})> {}",
+            nickname, name, reason, text
+        );
+        Some(format!(
+            "Your messages are not relayed to IRC because {}.",
+            reason
+        ))
+    }
+
+    fn status(&self) -> String {
+        let session = self.state.irc();
+        let mut lines = vec![format!(
+            "Server: `{}`",
+            self.irc_config.connection.server.as_deref().unwrap_or("?")
+        )];
+        match &session.nickname {
+            Some(nickname) if session.logged_in => {
+                lines.push(format!("Nickname: `{}`, logged in", nickname));
+            }
+            Some(nickname) => lines.push(format!("Nickname: `{}`", nickname)),
+            None => lines.push("Not connected".to_string()),
+        }
+        if session.joined {
+            lines.push(format!(
+                "In **{}** with {} users",
+                self.irc_config.channel,
+                self.state.roster().members().len()
+            ));
+        } else {
+            lines.push(format!("Not in **{}**", self.irc_config.channel));
+        }
+        if !session.enabled_caps.is_empty() {
+            let mut caps: Vec<_> = session.enabled_caps.iter().map(String::as_str).collect();
+            caps.sort_unstable();
+            lines.push(format!("Capabilities: {}", caps.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+fn string_option<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|option| option.name == name)?
+        .value
+        .as_ref()?
+        .as_str()
+}
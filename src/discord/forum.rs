@@ -0,0 +1,83 @@
+use serenity::model::channel::{GuildChannel, Message};
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+
+use super::DiscordHandler;
+use super::thread::thread_of;
+use crate::store::now;
+
+/// Threads created longer ago than this number of seconds are not announced, as Discord also
+/// sends them when the bot gains access to them.
+const NEW_POST_WINDOW: i64 = 60;
+
+impl DiscordHandler {
+    /// Announces a new post of the bridged forum on IRC, with its title and tags.
+    pub(super) async fn announce_forum_post(&self, ctx: &Context, thread: GuildChannel) {
+        let Some(forum) = &self.config.forum else {
+            return;
+        };
+        if thread.parent_id != Some(ChannelId(forum.channel_id))
+            || now() - thread.id.created_at().unix_timestamp() > NEW_POST_WINDOW
+        {
+            return;
+        }
+
+        let number = match self.state.store.forum_post_number(thread.id.0) {
+            Ok(number) => number,
+            Err(e) => {
+                warn!("Failed to number forum post {}: {}", thread.id, e);
+                return;
+            }
+        };
+        let mut line = format!("[#{} new post] {}", number, thread.name);
+        let tags: Vec<_> = ctx
+            .cache
+            .guild_channel_field(forum.channel_id, |forum| forum.available_tags.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|tag| thread.applied_tags.contains(&tag.id))
+            .map(|tag| tag.name)
+            .collect();
+        if !tags.is_empty() {
+            line.push_str(&format!(" ({})", tags.join(", ")));
+        }
+        if let Some(owner_id) = thread.owner_id
+            && let Ok(owner) = owner_id.to_user(ctx).await
+        {
+            line.push_str(" by ");
+            line.push_str(&self.display_name(&owner.name));
+        }
+        line.push_str(&format!(" - reply with !reply {} <text>", number));
+
+        info!("DIS> {}", line);
+        self.state.push_bot_line(&forum.irc_channel);
+        if let Err(e) = self.irc_sender.send_notice(&forum.irc_channel, line) {
+            error!("Discord to IRC send error: {}", e);
+        }
+    }
+
+    /// Returns the IRC channel and the line prefix for `msg` if it was sent in a post of the bridged
+    /// forum.
+    pub(super) async fn forum_target(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+    ) -> Option<(String, String)> {
+        let forum = self.config.forum.as_ref()?;
+        let thread = thread_of(ctx, msg).await?;
+        if thread.parent_id != Some(ChannelId(forum.channel_id)) {
+            return None;
+        }
+        let number = match self.state.store.forum_post_number(thread.id.0) {
+            Ok(number) => number,
+            Err(e) => {
+                warn!("Failed to number forum post {}: {}", thread.id, e);
+                return None;
+            }
+        };
+        Some((
+            forum.irc_channel.clone(),
+            format!("[#{} {}] ", number, thread.name),
+        ))
+    }
+}
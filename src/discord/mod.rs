@@ -0,0 +1,582 @@
+mod commands;
+mod forum;
+mod names;
+mod permissions;
+mod reaction;
+mod thread;
+mod topic;
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libirc::client::Sender;
+use libirc::client::prelude::{Command as IrcCommand, Message as IrcMessage};
+use libirc::proto::message::Tag;
+use serenity::cache::Cache;
+use serenity::http::Http;
+use serenity::model::application::interaction::Interaction;
+use serenity::model::channel::{Channel, GuildChannel, Message, Reaction};
+use serenity::model::event::{MessageUpdateEvent, TypingStartEvent};
+use serenity::model::gateway::Ready;
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+use serenity::model::user::User;
+use serenity::prelude::*;
+use serenity::utils::{ContentSafeOptions, content_safe};
+use stopper::Stopper;
+
+use self::commands::IRC_COMMAND;
+use self::names::NAMES_COMMAND;
+use self::thread::{ThreadTarget, thread_of};
+use crate::admin;
+use crate::casemapping::CaseMapping;
+use crate::config::*;
+use crate::format::substitution;
+use crate::ignore::Source;
+use crate::loops::Author;
+use crate::state::{PendingDelivery, State};
+use crate::store::{MessageRecord, Origin, now};
+use crate::utils::{excerpt, insert_zero_width_spaces_into_nickname, normalize_irc_nickname};
+
+/// Number of characters of the original message shown with a reply.
+const REPLY_EXCERPT_LEN: usize = 40;
+
+pub struct DiscordHandler {
+    config: DiscordConfig,
+    irc_config: IrcConfig,
+    irc_sender: Sender,
+    state: Arc<State>,
+    stopper: Option<Stopper>,
+}
+
+impl DiscordHandler {
+    pub fn new(
+        config: DiscordConfig,
+        irc_config: IrcConfig,
+        irc_sender: Sender,
+        state: Arc<State>,
+        stopper: Option<Stopper>,
+    ) -> Self {
+        DiscordHandler {
+            config,
+            irc_config,
+            irc_sender,
+            state,
+            stopper,
+        }
+    }
+
+    /// Sends a line to an IRC channel on behalf of a Discord user. Returns whether it was sent.
+    fn send_line(
+        &self,
+        channel: &str,
+        id: u64,
+        name: &str,
+        display_name: &str,
+        line: &str,
+    ) -> bool {
+        let command = if self.irc_config.ozinger.is_some() {
+            IrcCommand::Raw(
+                "FAKEMSG".to_string(),
+                vec![
+                    format!(
+                        "{}＠d!{:x}@pbzweihander/discord-irc-rs",
+                        normalize_irc_nickname(name),
+                        id
+                    ),
+                    channel.to_string(),
+                    line.to_string(),
+                ],
+            )
+        } else {
+            IrcCommand::PRIVMSG(channel.to_string(), format!("<{}> {}", display_name, line))
+        };
+        let Some(command) = self.state.queue_until_joined(channel, command) else {
+            return true;
+        };
+        if let Err(e) = self.irc_sender.send(command) {
+            error!("Discord to IRC send error: {}", e);
+            if let Some(stopper) = &self.stopper {
+                stopper.stop();
+            }
+            false
+        } else {
+            true
+        }
+    }
+
+    fn display_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.irc_config.prevent_noti_by_nicknames {
+            Cow::Owned(insert_zero_width_spaces_into_nickname(name))
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    fn announce_deletion(&self, message_id: MessageId) {
+        let Some(deletions) = &self.config.deletions else {
+            return;
+        };
+        let original = match self.state.store.remove(message_id.0) {
+            Ok(Some(original)) if original.origin == Origin::Discord => original,
+            Ok(_) => return,
+            Err(e) => {
+                warn!("Failed to look up message {}: {}", message_id, e);
+                return;
+            }
+        };
+        if original.age() > deletions.window {
+            return;
+        }
+
+        let notice = format!(
+            "[{} deleted a message]",
+            self.display_name(&original.author)
+        );
+        info!("DIS> {}", notice);
+        self.state.push_bot_line(&self.irc_config.channel);
+        if let Err(e) = self
+            .irc_sender
+            .send_notice(&self.irc_config.channel, notice)
+        {
+            error!("Discord to IRC send error: {}", e);
+            if let Some(stopper) = &self.stopper {
+                stopper.stop();
+            }
+        }
+    }
+
+    /// Renders the message a Discord message replies to, like `alice: ↪ "original text…" `. The
+    /// author highlights the IRC user if the original message came from IRC.
+    async fn reply_prefix(&self, http: &Http, cache: &Cache, referenced: &Message) -> String {
+        let (name, content) = if referenced.webhook_id == Some(self.config.webhook_id.into()) {
+            // The webhook posts under the IRC nickname, which may show the privilege of the user,
+            // and we have the original IRC text.
+            match self.state.store.by_discord_id(referenced.id.0) {
+                Ok(Some(record)) => (record.author, record.content),
+                _ => (referenced.author.name.clone(), referenced.content.clone()),
+            }
+        } else {
+            let name = referenced
+                .author_nick(http)
+                .await
+                .unwrap_or_else(|| referenced.author.name.clone());
+            (
+                insert_zero_width_spaces_into_nickname(&name),
+                referenced.content_safe(cache),
+            )
+        };
+
+        let quote = excerpt(&content, REPLY_EXCERPT_LEN);
+        if quote.is_empty() {
+            format!("{}: ↪ ", name)
+        } else {
+            format!("{}: ↪ \"{}\" ", name, quote)
+        }
+    }
+
+    /// Relays a message to `irc_channel`, with `prefix` before every line.
+    async fn relay_message(
+        &self,
+        ctx: Context,
+        msg: Message,
+        irc_channel: &str,
+        prefix: Option<String>,
+    ) {
+        if self.state.settings().paused {
+            debug!("DIS| <{}(paused)> {}", msg.author.name, msg.content);
+            return;
+        }
+        let Context { http, cache, .. } = ctx;
+
+        let content = msg.content_safe(&cache);
+        let id = msg.author.id.0;
+        let role_ids: Vec<_> = msg
+            .member
+            .iter()
+            .flat_map(|member| member.roles.iter().map(|role| role.0))
+            .collect();
+        if let Some(rule) = self.ignore_rule(&msg.author, &role_ids, &msg.content) {
+            debug!(
+                "DIS| Message of {} matched the ignore rule {}",
+                msg.author.name, rule
+            );
+            return;
+        }
+        if let Some(loops) = &self.state.loops {
+            let author_id = msg.webhook_id.map_or(id, |webhook_id| webhook_id.0);
+            if let Some(bridge) =
+                loops.echo(Origin::Discord, Author::Discord(author_id), &msg.content)
+            {
+                debug!(
+                    "DIS| <{}(echo from {})> {}",
+                    msg.author.name, bridge, msg.content
+                );
+                return;
+            }
+        }
+        if !self.may_relay(&http, &msg, &role_ids).await {
+            return;
+        }
+        let name = msg.author_nick(&http).await.unwrap_or(msg.author.name);
+        let display_name = self.display_name(&name);
+
+        let reply_prefix = match &msg.referenced_message {
+            Some(referenced) => Some(self.reply_prefix(&http, &cache, referenced).await),
+            None => None,
+        };
+
+        let lines = content
+            .split('\n')
+            .map(Cow::Borrowed)
+            .chain(msg.attachments.into_iter().map(|at| at.url).map(Into::into))
+            .enumerate()
+            .map(|(idx, line)| match &reply_prefix {
+                Some(reply_prefix) if idx == 0 => Cow::Owned(format!("{}{}", reply_prefix, line)),
+                _ => line,
+            })
+            .map(|line| match &prefix {
+                Some(prefix) => Cow::Owned(format!("{}{}", prefix, line)),
+                None => line,
+            });
+
+        if self.state.settings().discord_ignores.contains(&name) {
+            for line in lines {
+                debug!("DIS| <{}(ignored)> {}", name, line);
+            }
+        } else {
+            let lines: Vec<_> = lines.collect();
+            // Echoes give the IRC ID of the message, and report its delivery. Without
+            // `echo-message` the server only answers refused lines, which could not be told
+            // apart. FAKEMSG lines are not echoed.
+            if self.irc_config.ozinger.is_none()
+                && self.state.irc().enabled_caps.contains("echo-message")
+            {
+                self.state.push_delivery(PendingDelivery {
+                    irc_channel: irc_channel.to_string(),
+                    message: Some((msg.channel_id.0, msg.id.0)),
+                    lines: lines.len(),
+                    irc_msgid: None,
+                    refusal: None,
+                    sent_at: Instant::now(),
+                });
+            }
+            for line in lines {
+                info!("DIS> <{}> {}", name, line);
+                if !self.send_line(irc_channel, id, &name, &display_name, &line) {
+                    break;
+                }
+            }
+            if let Some(loops) = &self.state.loops {
+                for line in content.split('\n') {
+                    loops.record(Origin::Discord, line);
+                }
+            }
+            // Edits, deletions and reactions are only relayed for the bridged channel.
+            if msg.channel_id != self.config.channel_id {
+                return;
+            }
+            let record = MessageRecord {
+                discord_id: msg.id.0,
+                irc_msgid: None,
+                origin: Origin::Discord,
+                author_id: Some(id),
+                author: name,
+                content,
+                timestamp: now(),
+            };
+            if let Err(e) = self.state.store.insert(&record) {
+                warn!("Failed to store message {}: {}", msg.id, e);
+            }
+        }
+    }
+
+    /// Returns whether the author of `msg` may use admin commands.
+    fn is_admin(&self, msg: &Message) -> bool {
+        let Some(admins) = &self.config.admins else {
+            return false;
+        };
+        admins.users.contains(&msg.author.id.0)
+            || msg.member.as_ref().is_some_and(|member| {
+                member
+                    .roles
+                    .iter()
+                    .any(|role| admins.roles.contains(&role.0))
+            })
+    }
+
+    /// Returns the name of the ignore rule matching a message of `user` with `role_ids`.
+    fn ignore_rule(&self, user: &User, role_ids: &[u64], content: &str) -> Option<&str> {
+        self.state.ignore_rules.matching(&Source {
+            direction: IgnoreDirection::DiscordToIrc,
+            nickname: &user.name,
+            casemapping: CaseMapping::Ascii,
+            user_id: Some(user.id.0),
+            role_ids,
+            content,
+            ..Default::default()
+        })
+    }
+
+    /// Returns whether messages of the author of `msg` are relayed. Bots and webhooks are only
+    /// relayed when allowed, and never the bridge itself, which would loop.
+    fn is_relayed_author(&self, ctx: &Context, msg: &Message) -> bool {
+        if msg.webhook_id == Some(self.config.webhook_id.into())
+            || msg.author.id == ctx.cache.current_user_id()
+        {
+            return false;
+        }
+        if !msg.author.bot {
+            return true;
+        }
+        let Some(bots) = &self.config.bots else {
+            return false;
+        };
+        match msg.webhook_id {
+            Some(webhook_id) => bots.webhooks.contains(&webhook_id.0),
+            None => bots.users.contains(&msg.author.id.0),
+        }
+    }
+
+    /// Returns the IRC user whom a message in the private messages channel answers to.
+    async fn private_message_target(&self, ctx: &Context, msg: &Message) -> Option<String> {
+        let config = self.config.private_messages.as_ref()?;
+        {
+            let chats = self.state.private_chats();
+            if msg.channel_id == config.channel_id {
+                let reference = msg.message_reference.as_ref()?.message_id?;
+                return chats.nickname_of_message(reference.0).map(Into::into);
+            }
+            if !config.use_threads {
+                return None;
+            }
+            if let Some(nickname) = chats.nickname_of_thread(msg.channel_id.0) {
+                return Some(nickname.to_string());
+            }
+        }
+        // Threads created before a restart are named after the IRC user.
+        let thread = thread_of(ctx, msg).await?;
+        (thread.parent_id == Some(ChannelId(config.channel_id))).then_some(thread.name)
+    }
+
+    async fn reply_privately(&self, ctx: Context, msg: Message, nickname: String) {
+        let content = msg.content_safe(&ctx.cache);
+        let name = msg.author_nick(&ctx.http).await.unwrap_or(msg.author.name);
+        let lines = content
+            .split('\n')
+            .map(Cow::Borrowed)
+            .chain(msg.attachments.into_iter().map(|at| at.url).map(Into::into));
+
+        for line in lines {
+            info!("DIS> [PM to {}] <{}> {}", nickname, name, line);
+            if let Err(e) = self
+                .irc_sender
+                .send_privmsg(&nickname, format!("<{}> {}", name, line))
+            {
+                error!("Discord to IRC send error: {}", e);
+                if let Some(stopper) = &self.stopper {
+                    stopper.stop();
+                }
+            }
+        }
+    }
+}
+
+#[serenity::async_trait]
+impl EventHandler for DiscordHandler {
+    async fn message(&self, ctx: Context, msg: Message) {
+        let relayed = self.is_relayed_author(&ctx, &msg);
+        if !msg.author.bot
+            && let Some(args) = admin::parse(&msg.content)
+            && self.is_admin(&msg)
+        {
+            let answer = admin::run(&self.state, &self.irc_sender, &msg.author.tag(), args)
+                .unwrap_or_else(|e| format!("Failed: {}", e));
+            if let Err(e) = msg.reply(&ctx, answer).await {
+                warn!("Failed to answer an admin command: {}", e);
+            }
+        } else if relayed && msg.channel_id == self.config.channel_id {
+            if self.answer_names_message(&ctx, &msg).await {
+                return;
+            }
+            self.relay_message(ctx, msg, &self.irc_config.channel, None)
+                .await;
+        } else if relayed && let Some(nickname) = self.private_message_target(&ctx, &msg).await {
+            self.reply_privately(ctx, msg, nickname).await;
+        } else if relayed && let Some(target) = self.thread_target(&ctx, &msg).await {
+            match target {
+                ThreadTarget::Prefix(thread_name) => {
+                    let prefix = format!("[{}] ", thread_name);
+                    self.relay_message(ctx, msg, &self.irc_config.channel, Some(prefix))
+                        .await;
+                }
+                ThreadTarget::Channel(irc_channel) => {
+                    self.relay_message(ctx, msg, &irc_channel, None).await;
+                }
+            }
+        } else if relayed && let Some((irc_channel, prefix)) = self.forum_target(&ctx, &msg).await {
+            self.relay_message(ctx, msg, &irc_channel, Some(prefix))
+                .await;
+        } else {
+            debug!("DIS> {:?}", msg);
+        }
+    }
+
+    async fn ready(&self, ctx: Context, _ready: Ready) {
+        self.register_commands(&ctx).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::ApplicationCommand(command) = interaction else {
+            return;
+        };
+        match command.data.name.as_str() {
+            NAMES_COMMAND => self.answer_names_command(&ctx, &command).await,
+            IRC_COMMAND => self.answer_irc_command(&ctx, &command).await,
+            _ => {}
+        }
+    }
+
+    async fn channel_update(&self, _ctx: Context, old: Option<Channel>, new: Channel) {
+        self.sync_topic(old, new);
+    }
+
+    async fn thread_create(&self, ctx: Context, thread: GuildChannel) {
+        self.announce_forum_post(&ctx, thread).await;
+    }
+
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let Some(edits) = &self.config.edits else {
+            return;
+        };
+        if event.channel_id != self.config.channel_id {
+            return;
+        }
+        let content = match (&new, event.content) {
+            (Some(new), _) => new.content_safe(&ctx.cache),
+            // Resolve mentions like `Message::content_safe`, which leaves channels as they are.
+            (None, Some(content)) => content_safe(
+                &ctx.cache,
+                content,
+                &ContentSafeOptions::default().clean_channel(false),
+                event.mentions.as_deref().unwrap_or_default(),
+            ),
+            // Embeds were resolved, the content did not change.
+            (None, None) => return,
+        };
+        let original = match self.state.store.by_discord_id(event.id.0) {
+            Ok(Some(original)) if original.origin == Origin::Discord => original,
+            Ok(_) => return,
+            Err(e) => {
+                warn!("Failed to look up message {}: {}", event.id, e);
+                return;
+            }
+        };
+        if original.content == content {
+            return;
+        }
+        if let Err(e) = self.state.store.set_content(event.id.0, &content) {
+            warn!("Failed to store message {}: {}", event.id, e);
+        }
+        if original.age() > edits.window {
+            debug!("DIS| <{}(edit too late)> {}", original.author, content);
+            return;
+        }
+
+        let display_name = self.display_name(&original.author);
+        let diff = match edits.format {
+            EditFormat::Diff => substitution(&original.content, &content),
+            EditFormat::Full => None,
+        };
+        let lines: Vec<_> = match diff {
+            Some(diff) => vec![diff],
+            None => content
+                .split('\n')
+                .map(|line| format!("* corrected: {}", line))
+                .collect(),
+        };
+        for line in lines {
+            info!("DIS> <{}> {}", original.author, line);
+            // FAKEMSG lines are not echoed.
+            if self.irc_config.ozinger.is_none() {
+                self.state.push_bot_line(&self.irc_config.channel);
+            }
+            self.send_line(
+                &self.irc_config.channel,
+                original.author_id.unwrap_or_default(),
+                &original.author,
+                &display_name,
+                &line,
+            );
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
+        self.relay_reaction(&ctx, add_reaction).await;
+    }
+
+    async fn typing_start(&self, ctx: Context, event: TypingStartEvent) {
+        let Some(typing) = &self.config.typing else {
+            return;
+        };
+        if event.channel_id != self.config.channel_id
+            || event.user_id == ctx.cache.current_user_id()
+            || !self.state.irc().enabled_caps.contains("message-tags")
+        {
+            return;
+        }
+        if let Some(user) = ctx.cache.user(event.user_id)
+            && (user.bot || self.state.settings().discord_ignores.contains(&user.name))
+        {
+            return;
+        }
+        if !self
+            .state
+            .typing_on_irc(Duration::from_secs(typing.irc_interval))
+        {
+            return;
+        }
+
+        let message = IrcMessage {
+            tags: Some(vec![Tag("+typing".to_string(), Some("active".to_string()))]),
+            prefix: None,
+            command: IrcCommand::Raw("TAGMSG".to_string(), vec![self.irc_config.channel.clone()]),
+        };
+        self.state.push_bot_line(&self.irc_config.channel);
+        if let Err(e) = self.irc_sender.send(message) {
+            error!("Discord to IRC send error: {}", e);
+        }
+    }
+
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        if channel_id == self.config.channel_id {
+            self.announce_deletion(deleted_message_id);
+        }
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        _guild_id: Option<GuildId>,
+    ) {
+        if channel_id == self.config.channel_id {
+            for message_id in multiple_deleted_messages_ids {
+                self.announce_deletion(message_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+
+use super::DiscordHandler;
+use crate::roster::{Member, Roster};
+
+pub(super) const NAMES_COMMAND: &str = "irc-names";
+
+impl DiscordHandler {
+    /// Answers `/irc-names` with the roster of the IRC channel, only visible to the user.
+    pub(super) async fn answer_names_command(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) {
+        let Some(config) = &self.config.names else {
+            return;
+        };
+        let page = command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == "page")
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_u64())
+            .unwrap_or(1) as usize;
+        let content = render_names(
+            &self.state.roster(),
+            &self.irc_config.channel,
+            page,
+            config.page_size,
+        );
+        let result = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|data| data.content(content).ephemeral(true))
+            })
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to answer /{}: {}", NAMES_COMMAND, e);
+        }
+    }
+
+    /// Answers `!names [page]` in the bridged channel. Returns whether `msg` was the command.
+    pub(super) async fn answer_names_message(&self, ctx: &Context, msg: &Message) -> bool {
+        let Some(config) = &self.config.names else {
+            return false;
+        };
+        let mut words = msg.content.split_whitespace();
+        if words.next() != Some("!names") {
+            return false;
+        }
+        let page = words.next().and_then(|page| page.parse().ok()).unwrap_or(1);
+        let content = render_names(
+            &self.state.roster(),
+            &self.irc_config.channel,
+            page,
+            config.page_size,
+        );
+        if let Err(e) = msg.reply(ctx, content).await {
+            warn!("Failed to answer !names: {}", e);
+        }
+        true
+    }
+}
+
+/// Lists the members of the IRC channel grouped by privilege, with the away ones apart.
+pub(super) fn render_names(
+    roster: &Roster,
+    channel: &str,
+    page: usize,
+    page_size: usize,
+) -> String {
+    let members = roster.members();
+    let page_size = page_size.max(1);
+    let pages = members.len().div_ceil(page_size).max(1);
+    let page = page.clamp(1, pages);
+
+    let mut content = format!("**{}** on IRC: {} users", channel, members.len());
+    if pages > 1 {
+        content.push_str(&format!(" (page {}/{})", page, pages));
+    }
+    let members = members.iter().skip((page - 1) * page_size).take(page_size);
+    let mut groups: [(&str, Vec<String>); 4] = [
+        ("Operators", Vec::new()),
+        ("Voiced", Vec::new()),
+        ("Users", Vec::new()),
+        ("Away", Vec::new()),
+    ];
+    for member in members {
+        let idx = match (member.away, member.modes.chars().next()) {
+            (true, _) => 3,
+            (false, None) => 2,
+            (false, Some('v')) => 1,
+            (false, Some(_)) => 0,
+        };
+        groups[idx].1.push(render_member(roster, member));
+    }
+    for (name, members) in groups {
+        if !members.is_empty() {
+            content.push_str(&format!("\n**{}**: {}", name, members.join(", ")));
+        }
+    }
+    content
+}
+
+fn render_member(roster: &Roster, member: &Member) -> String {
+    let mut name: String = roster.prefix_of(member).into_iter().collect();
+    for c in member.nickname.chars() {
+        // Nicknames often contain Markdown characters.
+        if matches!(c, '\\' | '*' | '_' | '~' | '|' | '`') {
+            name.push('\\');
+        }
+        name.push(c);
+    }
+    name
+}
+
+#[test]
+fn test_render_names() {
+    let mut roster = Roster::default();
+    roster.add_names("@alice +bob carol dave_");
+    roster.set_away("carol", true);
+    assert_eq!(
+        render_names(&roster, "#rust", 1, 50),
+        "**#rust** on IRC: 4 users\n\
+         **Operators**: @alice\n\
+         **Voiced**: +bob\n\
+         **Users**: dave\\_\n\
+         **Away**: carol"
+    );
+    assert_eq!(
+        render_names(&roster, "#rust", 9, 3),
+        "**#rust** on IRC: 4 users (page 2/2)\n**Users**: dave\\_"
+    );
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use serenity::http::Http;
+use serenity::model::channel::Message;
+use serenity::model::timestamp::Timestamp;
+use serenity::model::user::User;
+
+use super::DiscordHandler;
+use crate::config::RelayPermissionsConfig;
+use crate::store::now;
+
+/// Minimum time between two notices to the same user.
+const NOTICE_INTERVAL: Duration = Duration::from_secs(3600);
+
+impl DiscordHandler {
+    /// Returns why `user`, with `role_ids`, who joined the server at `joined_at`, may not be
+    /// relayed to IRC.
+    pub(super) fn refusal_of(
+        &self,
+        user: &User,
+        role_ids: &[u64],
+        joined_at: Option<Timestamp>,
+    ) -> Option<&'static str> {
+        let config = self.config.relay_permissions.as_ref()?;
+        // Bots are only relayed when allowed, and have no roles or direct messages.
+        if user.bot {
+            return None;
+        }
+        refusal(
+            config,
+            role_ids,
+            user.id.created_at().unix_timestamp(),
+            joined_at.map(|joined_at| joined_at.unix_timestamp()),
+            now(),
+        )
+    }
+
+    /// Returns whether the author of a message may be relayed to IRC, telling them privately
+    /// when not.
+    pub(super) async fn may_relay(&self, http: &Http, msg: &Message, role_ids: &[u64]) -> bool {
+        let Some(config) = &self.config.relay_permissions else {
+            return true;
+        };
+        let joined_at = msg.member.as_ref().and_then(|member| member.joined_at);
+        let Some(reason) = self.refusal_of(&msg.author, role_ids, joined_at) else {
+            return true;
+        };
+        debug!(
+            "DIS| <{}(refused: {})> {}",
+            msg.author.name, reason, msg.content
+        );
+
+        if !config.notice.is_empty() && self.state.refusal_noticed(msg.author.id.0, NOTICE_INTERVAL)
+        {
+            let notice = config.notice.replace("{reason}", reason);
+            if let Err(e) = msg.author.direct_message(http, |m| m.content(notice)).await {
+                warn!(
+                    "Failed to tell {} why they are not relayed: {}",
+                    msg.author.name, e
+                );
+            }
+        }
+        false
+    }
+}
+
+/// Returns why a member with `role_ids`, whose account was created at `created_at` and who joined
+/// at `joined_at`, may not be relayed.
+fn refusal(
+    config: &RelayPermissionsConfig,
+    role_ids: &[u64],
+    created_at: i64,
+    joined_at: Option<i64>,
+    now: i64,
+) -> Option<&'static str> {
+    if role_ids
+        .iter()
+        .any(|role| config.blocked_roles.contains(role))
+    {
+        Some("one of your roles is not allowed to talk on IRC")
+    } else if !config.allowed_roles.is_empty()
+        && !role_ids
+            .iter()
+            .any(|role| config.allowed_roles.contains(role))
+    {
+        Some("none of your roles is allowed to talk on IRC")
+    } else if now - created_at < config.min_account_age as i64 {
+        Some("your Discord account is too new")
+    } else if now - joined_at.unwrap_or(now) < config.min_member_age as i64 {
+        Some("you joined the server too recently")
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_refusal() {
+    let config = RelayPermissionsConfig {
+        allowed_roles: vec![1, 2],
+        blocked_roles: vec![3],
+        min_account_age: 86400,
+        min_member_age: 3600,
+        notice: String::new(),
+    };
+    let day_ago = 1_000_000 - 86400;
+    assert_eq!(
+        refusal(&config, &[2], day_ago, Some(day_ago), 1_000_000),
+        None
+    );
+    assert!(refusal(&config, &[2, 3], day_ago, Some(day_ago), 1_000_000).is_some());
+    assert!(refusal(&config, &[4], day_ago, Some(day_ago), 1_000_000).is_some());
+    assert!(refusal(&config, &[1], day_ago + 1, Some(day_ago), 1_000_000).is_some());
+    assert!(refusal(&config, &[1], day_ago, Some(999_000), 1_000_000).is_some());
+    assert!(refusal(&config, &[1], day_ago, None, 1_000_000).is_some());
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use libirc::client::prelude::{Command as IrcCommand, Message as IrcMessage};
+use libirc::proto::message::Tag;
+use serenity::model::channel::{Reaction, ReactionType};
+use serenity::prelude::*;
+
+use super::DiscordHandler;
+use crate::state::PendingReactions;
+use crate::store::Origin;
+use crate::utils::{excerpt, insert_zero_width_spaces_into_nickname};
+
+/// Number of characters of the original message shown with reactions.
+const REACTION_EXCERPT_LEN: usize = 30;
+
+impl DiscordHandler {
+    /// Relays a reaction to a bridged message, as an IRCv3 reaction if the server supports client
+    /// tags and we know the IRC ID of the message, or else as a line announcing the reactions made
+    /// within the throttle period.
+    pub(super) async fn relay_reaction(&self, ctx: &Context, reaction: Reaction) {
+        let Some(config) = &self.config.reactions else {
+            return;
+        };
+        if reaction.channel_id != self.config.channel_id
+            || reaction.user_id == Some(ctx.cache.current_user_id())
+        {
+            return;
+        }
+        let original = match self.state.store.by_discord_id(reaction.message_id.0) {
+            Ok(Some(original)) => original,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to look up message {}: {}", reaction.message_id, e);
+                return;
+            }
+        };
+
+        let emoji = emoji_text(&reaction.emoji);
+        let member = reaction.member.as_ref();
+        let user = match member.and_then(|member| member.user.clone()) {
+            Some(user) => user,
+            None => match reaction.user(ctx).await {
+                Ok(user) => user,
+                Err(e) => {
+                    warn!("Failed to get the user of a reaction: {}", e);
+                    return;
+                }
+            },
+        };
+        if user.bot {
+            return;
+        }
+        let name = member
+            .and_then(|member| member.nick.clone())
+            .unwrap_or_else(|| user.name.clone());
+        if self.state.settings().paused {
+            debug!("DIS| <{}(paused)> reacted {}", name, emoji);
+            return;
+        }
+        if self.state.settings().discord_ignores.contains(&name) {
+            debug!("DIS| <{}(ignored)> reacted {}", name, emoji);
+            return;
+        }
+        let role_ids: Vec<_> = member
+            .iter()
+            .flat_map(|member| member.roles.iter().map(|role| role.0))
+            .collect();
+        if let Some(rule) = self.ignore_rule(&user, &role_ids, &emoji) {
+            debug!("DIS| Reaction of {} matched the ignore rule {}", name, rule);
+            return;
+        }
+        let joined_at = member.and_then(|member| member.joined_at);
+        if let Some(reason) = self.refusal_of(&user, &role_ids, joined_at) {
+            debug!("DIS| <{}(refused: {})> reacted {}", name, reason, emoji);
+            return;
+        }
+
+        let client_tags = self.state.irc().enabled_caps.contains("message-tags");
+        if config.use_tags
+            && client_tags
+            && let Some(irc_msgid) = &original.irc_msgid
+        {
+            info!("DIS> {} reacted {} to {}", name, emoji, irc_msgid);
+            let message = IrcMessage {
+                tags: Some(vec![
+                    Tag("+draft/reply".to_string(), Some(irc_msgid.clone())),
+                    Tag("+draft/react".to_string(), Some(emoji)),
+                ]),
+                prefix: None,
+                command: IrcCommand::Raw(
+                    "TAGMSG".to_string(),
+                    vec![self.irc_config.channel.clone()],
+                ),
+            };
+            self.state.push_bot_line(&self.irc_config.channel);
+            if let Err(e) = self.irc_sender.send(message) {
+                error!("Discord to IRC send error: {}", e);
+            }
+            return;
+        }
+
+        let message_id = original.discord_id;
+        let name = self.display_name(&name).into_owned();
+        {
+            let mut pending = self.state.reactions();
+            if let Some(reactions) = pending.get_mut(&message_id) {
+                reactions.add(emoji, name);
+                return;
+            }
+            let mut reactions = PendingReactions::new(original);
+            reactions.add(emoji, name);
+            pending.insert(message_id, reactions);
+        }
+
+        let throttle = Duration::from_secs(config.throttle);
+        let state = self.state.clone();
+        let irc_sender = self.irc_sender.clone();
+        let channel = self.irc_config.channel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(throttle).await;
+            let Some(reactions) = state.reactions().remove(&message_id) else {
+                return;
+            };
+            let line = render(&reactions);
+            info!("DIS> {}", line);
+            state.push_bot_line(&channel);
+            if let Err(e) = irc_sender.send_privmsg(&channel, line) {
+                error!("Discord to IRC send error: {}", e);
+            }
+        });
+    }
+}
+
+fn emoji_text(emoji: &ReactionType) -> String {
+    match emoji {
+        ReactionType::Unicode(emoji) => emoji.clone(),
+        ReactionType::Custom {
+            name: Some(name), ..
+        } => format!(":{}:", name),
+        _ => "an emoji".to_string(),
+    }
+}
+
+/// Renders reactions like `* carol, dave reacted 👍 to alice's "deploy done"`.
+fn render(pending: &PendingReactions) -> String {
+    let reactions: Vec<_> = pending
+        .reactions
+        .iter()
+        .map(|(emoji, users)| format!("{} reacted {}", users.join(", "), emoji))
+        .collect();
+    // Highlight IRC users when someone reacts to their message.
+    let author = match pending.original.origin {
+        Origin::Irc => pending.original.author.clone(),
+        Origin::Discord => insert_zero_width_spaces_into_nickname(&pending.original.author),
+    };
+    format!(
+        "* {} to {}'s \"{}\"",
+        reactions.join(" and "),
+        author,
+        excerpt(&pending.original.content, REACTION_EXCERPT_LEN)
+    )
+}
+
+#[test]
+fn test_render() {
+    use crate::store::MessageRecord;
+
+    let mut pending = PendingReactions::new(MessageRecord {
+        discord_id: 1,
+        irc_msgid: None,
+        origin: Origin::Irc,
+        author_id: None,
+        author: "alice".to_string(),
+        content: "deploy done".to_string(),
+        timestamp: 0,
+    });
+    pending.add("👍".to_string(), "carol".to_string());
+    assert_eq!(
+        render(&pending),
+        "* carol reacted 👍 to alice's \"deploy done\""
+    );
+    pending.add("👍".to_string(), "dave".to_string());
+    pending.add("👍".to_string(), "carol".to_string());
+    pending.add(":party:".to_string(), "erin".to_string());
+    assert_eq!(
+        render(&pending),
+        "* carol, dave reacted 👍 and erin reacted :party: to alice's \"deploy done\""
+    );
+}
@@ -0,0 +1,111 @@
+use serenity::model::channel::{Channel, ChannelType, GuildChannel, Message};
+use serenity::model::id::ChannelId;
+use serenity::prelude::*;
+
+use super::DiscordHandler;
+use crate::config::ThreadMode;
+
+/// Where a message sent in a thread of the bridged channel goes on IRC.
+pub(super) enum ThreadTarget {
+    /// The bridged IRC channel, with the name of the thread as a prefix.
+    Prefix(String),
+    /// The IRC channel of the thread.
+    Channel(String),
+}
+
+impl DiscordHandler {
+    /// Returns where to relay `msg` on IRC if it was sent in a thread of the bridged channel,
+    /// joining the IRC channel of the thread if needed.
+    pub(super) async fn thread_target(&self, ctx: &Context, msg: &Message) -> Option<ThreadTarget> {
+        let config = self.config.threads.as_ref()?;
+        if config.mode == ThreadMode::Channels
+            && let Some(irc_channel) = self.state.thread_channels().get(&msg.channel_id.0)
+        {
+            return Some(ThreadTarget::Channel(irc_channel.clone()));
+        }
+
+        let thread = thread_of(ctx, msg).await?;
+        if thread.parent_id != Some(ChannelId(self.config.channel_id)) {
+            return None;
+        }
+        match config.mode {
+            ThreadMode::Prefix => Some(ThreadTarget::Prefix(thread.name)),
+            ThreadMode::Channels => {
+                let irc_channel = thread_channel_name(
+                    &config.channel_name,
+                    &self.irc_config.channel,
+                    &thread.name,
+                );
+                info!("DIS| Joining {} for thread {}", irc_channel, thread.name);
+                self.state.start_joining(&irc_channel);
+                if let Err(e) = self.irc_sender.send_join(&irc_channel) {
+                    error!("IRC| Failed to join {}: {}", irc_channel, e);
+                    self.state.finish_joining(&irc_channel);
+                    return None;
+                }
+                self.state
+                    .thread_channels()
+                    .insert(msg.channel_id.0, irc_channel.clone());
+                Some(ThreadTarget::Channel(irc_channel))
+            }
+        }
+    }
+}
+
+/// Returns the thread a message was sent in, if it was sent in one.
+pub(super) async fn thread_of(ctx: &Context, msg: &Message) -> Option<GuildChannel> {
+    // The cache keeps threads apart from the other channels.
+    let cached = msg
+        .guild_id
+        .and_then(|guild_id| {
+            ctx.cache
+                .guild_field(guild_id, |guild| guild.threads.clone())
+        })
+        .and_then(|threads| threads.into_iter().find(|t| t.id == msg.channel_id));
+    let channel = match cached {
+        Some(thread) => thread,
+        None => match msg.channel_id.to_channel(ctx).await {
+            Ok(Channel::Guild(channel)) => channel,
+            _ => return None,
+        },
+    };
+    matches!(
+        channel.kind,
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+    )
+    .then_some(channel)
+}
+
+/// Names the IRC channel of a thread after `template`. Characters IRC does not allow in channel
+/// names are replaced.
+fn thread_channel_name(template: &str, irc_channel: &str, thread_name: &str) -> String {
+    let thread_name: String = thread_name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            ' ' | ',' | ':' | '\u{7}' => '-',
+            c if c.is_control() => '-',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect();
+    let mut name = template
+        .replace("{channel}", irc_channel)
+        .replace("{thread}", &thread_name);
+    // Most servers limit channel names to 50 bytes.
+    while name.len() > 50 {
+        name.pop();
+    }
+    name
+}
+
+#[test]
+fn test_thread_channel_name() {
+    let f = thread_channel_name;
+    assert_eq!(f("{channel}-{thread}", "#rust", "Help"), "#rust-help");
+    assert_eq!(
+        f("{channel}-{thread}", "#rust", "Release 1.0: notes, etc"),
+        "#rust-release-1.0--notes--etc"
+    );
+    assert_eq!(f("#t-{thread}", "#rust", "배포"), "#t-배포");
+    assert_eq!(f("{channel}-{thread}", "#rust", &"a".repeat(60)).len(), 50);
+}
@@ -0,0 +1,53 @@
+use serenity::model::channel::Channel;
+
+use super::DiscordHandler;
+
+impl DiscordHandler {
+    /// Copies the topic of the Discord channel to IRC when it changes, unless IRC already has it.
+    pub(super) fn sync_topic(&self, old: Option<Channel>, new: Channel) {
+        let Some(config) = &self.config.topic else {
+            return;
+        };
+        let Channel::Guild(new) = new else {
+            return;
+        };
+        if new.id != self.config.channel_id {
+            return;
+        }
+
+        let topic = new.topic.unwrap_or_default();
+        let previous = match old {
+            Some(Channel::Guild(old)) => Some(old.topic.unwrap_or_default()),
+            _ => None,
+        };
+        {
+            let mut topics = self.state.topics();
+            let previous = previous.or_else(|| topics.discord.clone());
+            topics.discord = Some(topic.clone());
+            // Without the previous topic, another setting of the channel may have changed.
+            if previous.is_none_or(|previous| previous == topic)
+                || !config.direction.to_irc()
+                || topics.irc.as_ref() == Some(&topic)
+            {
+                return;
+            }
+            topics.irc = Some(topic.clone());
+        }
+
+        info!("DIS> Topic changed to: {}", topic);
+        let channel = &self.irc_config.channel;
+        let mut result = self.irc_sender.send_topic(channel, &topic);
+        if result.is_ok() && config.announce {
+            let notice = if topic.is_empty() {
+                "[The topic was cleared on Discord]".to_string()
+            } else {
+                format!("[The topic was changed on Discord to: {}]", topic)
+            };
+            self.state.push_bot_line(channel);
+            result = self.irc_sender.send_notice(channel, notice);
+        }
+        if let Err(e) = result {
+            error!("Discord to IRC send error: {}", e);
+        }
+    }
+}
@@ -1,4 +1,7 @@
 mod irc_to_discord;
+mod sed;
+
+pub use sed::{Substitution, substitution};
 
 pub fn irc_msg_to_discord(message: impl AsRef<str>) -> String {
     irc_to_discord::Converter::convert(message)
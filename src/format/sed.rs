@@ -0,0 +1,160 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+
+/// A `s/pattern/replacement/flags` correction typed by an IRC user.
+#[derive(Debug)]
+pub struct Substitution {
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Substitution {
+    /// Parses a whole line as a substitution. Supports the `g` and `i` flags, and `\/` to escape
+    /// the delimiter.
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim_end().strip_prefix("s/")?;
+        let mut parts = Vec::with_capacity(3);
+        let mut part = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('/') => part.push('/'),
+                    Some(c) => {
+                        part.push('\\');
+                        part.push(c);
+                    }
+                    None => part.push('\\'),
+                },
+                '/' if parts.len() < 2 => parts.push(std::mem::take(&mut part)),
+                c => part.push(c),
+            }
+        }
+        parts.push(part);
+
+        let [pattern, replacement, flags] = match <[String; 3]>::try_from(parts) {
+            Ok(parts) => parts,
+            // The trailing delimiter is often omitted.
+            Err(parts) if parts.len() == 2 => {
+                let [pattern, replacement] = <[String; 2]>::try_from(parts).ok()?;
+                [pattern, replacement, String::new()]
+            }
+            Err(_) => return None,
+        };
+        if pattern.is_empty() || !flags.chars().all(|c| c == 'g' || c == 'i') {
+            return None;
+        }
+
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(flags.contains('i'))
+            .size_limit(1 << 16)
+            .build()
+            .ok()?;
+        Some(Substitution {
+            pattern,
+            replacement,
+            global: flags.contains('g'),
+        })
+    }
+
+    /// Applies the substitution, returning `None` if the pattern does not match.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if !self.pattern.is_match(text) {
+            return None;
+        }
+        let replacement = NoExpand(&self.replacement);
+        let replaced = if self.global {
+            self.pattern.replace_all(text, replacement)
+        } else {
+            self.pattern.replace(text, replacement)
+        };
+        Some(replaced.into_owned())
+    }
+}
+
+/// Describes the change from `old` to `new` as a `s/old/new/` substitution of whole words, the
+/// way IRC users correct themselves. Returns `None` if there is no change or the substitution
+/// would be ambiguous.
+pub fn substitution(old: &str, new: &str) -> Option<String> {
+    if old == new || old.contains('\n') || new.contains('\n') {
+        return None;
+    }
+
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((idx, _), _)| idx)
+        .unwrap_or_else(|| old.len().min(new.len()));
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .char_indices()
+        .rev()
+        .zip(new.chars().rev())
+        .take_while(|((idx, a), b)| a == b && old.len() - idx <= max_suffix)
+        .last()
+        .map(|((idx, _), _)| old.len() - idx)
+        .unwrap_or(0);
+
+    // Widen the changed part to whole words. Both strings share the prefix and the suffix, so the
+    // boundaries are found on `old` only.
+    let start = old[..prefix]
+        .rfind(char::is_whitespace)
+        .map(|idx| idx + old[idx..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(0);
+    let suffix_start = old.len() - suffix;
+    let word_end = old[suffix_start..]
+        .find(char::is_whitespace)
+        .unwrap_or(suffix);
+    let old_part = &old[start..suffix_start + word_end];
+    let new_part = &new[start..new.len() - suffix + word_end];
+
+    if old_part.is_empty()
+        || old_part.contains('/')
+        || new_part.contains('/')
+        || old.find(old_part) != Some(start)
+    {
+        return None;
+    }
+    Some(format!("s/{}/{}/", old_part, new_part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Substitution, substitution};
+
+    #[test]
+    fn parse_and_apply() {
+        let f = |line: &str, text: &str| Substitution::parse(line).and_then(|s| s.apply(text));
+
+        assert_eq!(f("s/teh/the/", "teh cat").as_deref(), Some("the cat"));
+        assert_eq!(f("s/teh/the", "teh cat").as_deref(), Some("the cat"));
+        assert_eq!(f("s/a/b/g", "a a").as_deref(), Some("b b"));
+        assert_eq!(f("s/A/b/", "a a").as_deref(), None);
+        assert_eq!(f("s/A/b/i", "a a").as_deref(), Some("b a"));
+        assert_eq!(f("s/1\\/2/half/", "1/2 cup").as_deref(), Some("half cup"));
+        assert_eq!(f("s/x/$1/", "x").as_deref(), Some("$1"));
+        assert_eq!(f("s/x/y/q", "x").as_deref(), None);
+        assert!(Substitution::parse("so/what/now").is_none());
+        assert!(Substitution::parse("s//y/").is_none());
+    }
+
+    #[test]
+    fn substitution_of_words() {
+        assert_eq!(
+            substitution("teh cat is here", "the cat is here").as_deref(),
+            Some("s/teh/the/"),
+        );
+        assert_eq!(
+            substitution("hello world", "hello there world").as_deref(),
+            Some("s/world/there world/"),
+        );
+        assert_eq!(
+            substitution("안녕 세계", "안녕 세상").as_deref(),
+            Some("s/세계/세상/")
+        );
+        assert_eq!(substitution("same", "same"), None);
+        assert_eq!(substitution("a b a", "a b c"), None);
+        assert_eq!(substitution("1/2", "1/3"), None);
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::casemapping::CaseMapping;
+use crate::config::{IgnoreDirection, IgnoreRuleConfig};
+use crate::utils::mask_matches;
+
+/// Author and content of a message about to be relayed.
+#[derive(Debug, Default)]
+pub struct Source<'a> {
+    pub direction: IgnoreDirection,
+    pub nickname: &'a str,
+    /// How nicknames are compared.
+    pub casemapping: CaseMapping,
+    /// `nick!user@host` of an IRC user.
+    pub hostmask: Option<&'a str>,
+    pub account: Option<&'a str>,
+    pub user_id: Option<u64>,
+    pub role_ids: &'a [u64],
+    pub content: &'a str,
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: IgnoreRuleConfig,
+    content: Option<Regex>,
+    hits: AtomicU64,
+}
+
+impl Rule {
+    fn matches(&self, source: &Source) -> bool {
+        let config = &self.config;
+        (config.direction == IgnoreDirection::Both || config.direction == source.direction)
+            && config.hostmask.as_ref().is_none_or(|mask| {
+                source
+                    .hostmask
+                    .is_some_and(|hostmask| mask_matches(mask, hostmask))
+            })
+            && config
+                .account
+                .as_ref()
+                .is_none_or(|account| source.account == Some(account.as_str()))
+            && config
+                .nickname
+                .as_ref()
+                .is_none_or(|nickname| source.casemapping.eq(nickname, source.nickname))
+            && config
+                .user_id
+                .is_none_or(|user_id| source.user_id == Some(user_id))
+            && config
+                .role_id
+                .is_none_or(|role_id| source.role_ids.contains(&role_id))
+            && self
+                .content
+                .as_ref()
+                .is_none_or(|content| content.is_match(source.content))
+    }
+}
+
+/// Ignore rules, counting the messages each one dropped.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    pub fn new(configs: Vec<IgnoreRuleConfig>) -> Result<Self> {
+        let rules = configs
+            .into_iter()
+            .map(|config| {
+                let content = match &config.content {
+                    Some(content) => Some(
+                        Regex::new(content)
+                            .with_context(|| format!("Invalid ignore rule {}", config.name))?,
+                    ),
+                    None => None,
+                };
+                Ok(Rule {
+                    config,
+                    content,
+                    hits: AtomicU64::new(0),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(IgnoreRules { rules })
+    }
+
+    /// Returns whether a rule needs the services account of IRC users.
+    pub fn uses_accounts(&self) -> bool {
+        self.rules.iter().any(|rule| rule.config.account.is_some())
+    }
+
+    /// Returns the name of the first rule ignoring `source`.
+    pub fn matching(&self, source: &Source) -> Option<&str> {
+        let rule = self.rules.iter().find(|rule| rule.matches(source))?;
+        rule.hits.fetch_add(1, Ordering::Relaxed);
+        Some(&rule.config.name)
+    }
+
+    /// Returns the name of every rule with the number of messages it ignored.
+    pub fn hits(&self) -> Vec<(&str, u64)> {
+        self.rules
+            .iter()
+            .map(|rule| (rule.config.name.as_str(), rule.hits.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+#[test]
+fn test_ignore_rules() -> Result<()> {
+    let rule = |name: &str| IgnoreRuleConfig {
+        name: name.to_string(),
+        direction: IgnoreDirection::Both,
+        hostmask: None,
+        account: None,
+        nickname: None,
+        user_id: None,
+        role_id: None,
+        content: None,
+    };
+    let rules = IgnoreRules::new(vec![
+        IgnoreRuleConfig {
+            hostmask: Some("*!*@bots.example.org".to_string()),
+            ..rule("bots")
+        },
+        IgnoreRuleConfig {
+            direction: IgnoreDirection::IrcToDiscord,
+            nickname: Some("[GitHub]".to_string()),
+            ..rule("github")
+        },
+        IgnoreRuleConfig {
+            direction: IgnoreDirection::DiscordToIrc,
+            role_id: Some(42),
+            content: Some("^!".to_string()),
+            ..rule("discord commands")
+        },
+    ])?;
+
+    let from_irc = Source {
+        direction: IgnoreDirection::IrcToDiscord,
+        nickname: "feed",
+        hostmask: Some("feed!~feed@Bots.example.org"),
+        content: "!news",
+        ..Default::default()
+    };
+    assert_eq!(rules.matching(&from_irc), Some("bots"));
+    let from_irc = Source {
+        hostmask: Some("alice!~a@example.org"),
+        ..from_irc
+    };
+    assert_eq!(rules.matching(&from_irc), None);
+    let from_irc = Source {
+        nickname: "{github}",
+        ..from_irc
+    };
+    assert_eq!(rules.matching(&from_irc), Some("github"));
+
+    let from_discord = Source {
+        direction: IgnoreDirection::DiscordToIrc,
+        nickname: "bob",
+        user_id: Some(1),
+        role_ids: &[42],
+        content: "!play",
+        ..Default::default()
+    };
+    assert_eq!(rules.matching(&from_discord), Some("discord commands"));
+    let from_discord = Source {
+        content: "play!",
+        ..from_discord
+    };
+    assert_eq!(rules.matching(&from_discord), None);
+
+    assert_eq!(
+        rules.hits(),
+        [("bots", 1), ("github", 1), ("discord commands", 1)]
+    );
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use libirc::client::prelude::{Client, Command, NegotiationVersion, Sender};
+use libirc::proto::CapSubCommand;
+
+use crate::config::{IrcConfig, SaslMechanism};
+use crate::state::{SaslStatus, State};
+
+/// Maximum length of a single `AUTHENTICATE` payload chunk.
+const SASL_CHUNK_LEN: usize = 400;
+
+/// Registers the connection, starting the IRCv3 capability negotiation first so that SASL can
+/// happen before the registration completes.
+pub fn identify(client: &Client, config: &IrcConfig) -> Result<()> {
+    client.send_cap_ls(NegotiationVersion::V302)?;
+    let connection = &config.connection;
+    if !connection.password().is_empty() {
+        client.send(Command::PASS(connection.password().to_owned()))?;
+    }
+    client.send(Command::NICK(connection.nickname()?.to_owned()))?;
+    client.send(Command::USER(
+        connection.username().to_owned(),
+        "0".to_owned(),
+        connection.real_name().to_owned(),
+    ))?;
+    Ok(())
+}
+
+fn wanted_caps(config: &IrcConfig, state: &State) -> Vec<&'static str> {
+    // `message-tags` gives us the IDs of IRC messages, and `echo-message` the IDs of the lines we
+    // send, which IRC replies and reactions to relayed Discord messages refer to.
+    // `multi-prefix` gives us every privilege of the members in NAMES replies, and
+    // `away-notify` tells us when they go away.
+    let mut caps = vec![
+        "message-tags",
+        "echo-message",
+        "multi-prefix",
+        "away-notify",
+    ];
+    if config.sasl.is_some() {
+        caps.push("sasl");
+    }
+    if config
+        .admins
+        .as_ref()
+        .is_some_and(|admins| !admins.accounts.is_empty())
+        || state.ignore_rules.uses_accounts()
+    {
+        caps.push("account-tag");
+    }
+    caps
+}
+
+pub fn handle_cap(
+    irc_sender: &Sender,
+    config: &IrcConfig,
+    state: &State,
+    subcommand: CapSubCommand,
+    arg: Option<String>,
+    last_arg: Option<String>,
+) -> Result<()> {
+    // `CAP * LS * :caps` marks a continued reply, `CAP * LS :caps` the last one.
+    let (is_continued, caps) = match (arg, last_arg) {
+        (Some(star), Some(caps)) if star == "*" => (true, caps),
+        (Some(caps), None) | (_, Some(caps)) => (false, caps),
+        (None, None) => (false, String::new()),
+    };
+    // Capabilities may carry values in 3.2 negotiation, e.g. `sasl=PLAIN,EXTERNAL`.
+    let names = caps
+        .split_whitespace()
+        .map(|cap| cap.split('=').next().unwrap_or(cap).to_owned());
+
+    match subcommand {
+        CapSubCommand::LS => {
+            let mut session = state.irc();
+            session.available_caps.extend(names);
+            if is_continued {
+                return Ok(());
+            }
+
+            let request: Vec<_> = wanted_caps(config, state)
+                .into_iter()
+                .filter(|cap| session.available_caps.contains(*cap))
+                .collect();
+            if config.sasl.is_some() && !request.contains(&"sasl") {
+                session.sasl = SaslStatus::Failed("server does not support SASL".to_string());
+            }
+            if request.is_empty() {
+                irc_sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+            } else {
+                irc_sender.send(Command::CAP(
+                    None,
+                    CapSubCommand::REQ,
+                    None,
+                    Some(request.join(" ")),
+                ))?;
+            }
+        }
+        CapSubCommand::ACK => {
+            let mut session = state.irc();
+            session.enabled_caps.extend(names);
+            match &config.sasl {
+                Some(sasl)
+                    if session.enabled_caps.contains("sasl")
+                        && session.sasl == SaslStatus::NotStarted =>
+                {
+                    session.sasl = SaslStatus::InProgress;
+                    irc_sender.send_sasl(sasl.mechanism.as_str())?;
+                }
+                _ => irc_sender.send(Command::CAP(None, CapSubCommand::END, None, None))?,
+            }
+        }
+        CapSubCommand::NAK => {
+            warn!("IRC| Server rejected capabilities: {}", caps);
+            let mut session = state.irc();
+            if config.sasl.is_some() && session.sasl == SaslStatus::NotStarted {
+                session.sasl =
+                    SaslStatus::Failed("server rejected the sasl capability".to_string());
+            }
+            irc_sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+        }
+        _ => {
+            debug!("IRC> CAP {:?} {}", subcommand, caps);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_authenticate(
+    irc_sender: &Sender,
+    config: &IrcConfig,
+    state: &State,
+    data: &str,
+) -> Result<()> {
+    let Some(sasl) = &config.sasl else {
+        return Ok(());
+    };
+    if data != "+" || state.irc().sasl != SaslStatus::InProgress {
+        return Ok(());
+    }
+
+    let payload = match sasl.mechanism {
+        SaslMechanism::Plain => {
+            let username = match &sasl.username {
+                Some(username) => username.as_str(),
+                None => config.connection.nickname()?,
+            };
+            format!("{}\0{}\0{}", username, username, sasl.password)
+        }
+        SaslMechanism::External => String::new(),
+    };
+    for chunk in sasl_chunks(&payload) {
+        irc_sender.send_sasl(chunk)?;
+    }
+    Ok(())
+}
+
+/// Finishes the negotiation after the server answered the authentication attempt.
+pub fn finish_sasl(irc_sender: &Sender, state: &State, result: Result<(), String>) -> Result<()> {
+    state.irc().sasl = match result {
+        Ok(()) => {
+            info!("IRC| SASL authentication succeeded");
+            SaslStatus::Succeeded
+        }
+        Err(reason) => {
+            error!("IRC| SASL authentication failed: {}", reason);
+            SaslStatus::Failed(reason)
+        }
+    };
+    irc_sender.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+    Ok(())
+}
+
+/// Encodes a SASL response, splitting it into `AUTHENTICATE` sized chunks. A response whose
+/// length is a multiple of the chunk length is terminated with an extra `+`.
+fn sasl_chunks(payload: &str) -> Vec<String> {
+    if payload.is_empty() {
+        return vec!["+".to_string()];
+    }
+    let encoded = BASE64.encode(payload);
+    let mut chunks: Vec<_> = encoded
+        .as_bytes()
+        .chunks(SASL_CHUNK_LEN)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+    if encoded.len().is_multiple_of(SASL_CHUNK_LEN) {
+        chunks.push("+".to_string());
+    }
+    chunks
+}
+
+#[test]
+fn test_sasl_chunks() {
+    assert_eq!(sasl_chunks(""), vec!["+"]);
+    assert_eq!(sasl_chunks("bot\0bot\0pw"), vec!["Ym90AGJvdABwdw=="]);
+
+    let chunks = sasl_chunks(&"a".repeat(600));
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0].len(), 400);
+    assert_eq!(chunks[1].len(), 400);
+    assert_eq!(chunks[2], "+");
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use libirc::client::Sender;
+use libirc::client::prelude::Response;
+
+use crate::config::IrcConfig;
+use crate::state::State;
+
+/// Asks the server to join the bridged channel, with its key if one is configured.
+pub fn join(irc_sender: &Sender, config: &IrcConfig, state: &State) -> Result<()> {
+    state.irc().join_sent = true;
+    match &config.channel_key {
+        Some(key) => irc_sender.send_join_with_keys(&config.channel, key)?,
+        None => irc_sender.send_join(&config.channel)?,
+    }
+    Ok(())
+}
+
+/// Schedules another attempt to join the channel, doubling the delay for every attempt made in
+/// quick succession.
+pub fn schedule_rejoin(irc_sender: &Sender, config: &IrcConfig, state: &Arc<State>) {
+    if !config.auto_rejoin {
+        return;
+    }
+
+    let max_delay = Duration::from_secs(config.max_rejoin_delay);
+    let delay = {
+        let mut session = state.irc();
+        let now = Instant::now();
+        match session.last_rejoin {
+            Some(last) if now.duration_since(last) < max_delay * 2 => {
+                session.rejoin_attempts += 1;
+            }
+            _ => session.rejoin_attempts = 0,
+        }
+        session.last_rejoin = Some(now);
+
+        let factor = 1u32 << session.rejoin_attempts.min(16);
+        (Duration::from_secs(config.rejoin_delay) * factor).min(max_delay)
+    };
+
+    info!("IRC| Rejoining {} in {:?}", config.channel, delay);
+    let irc_sender = irc_sender.clone();
+    let config = config.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if state.irc().joined {
+            return;
+        }
+        if let Err(e) = join(&irc_sender, &config, &state) {
+            error!("IRC| Failed to rejoin {}: {}", config.channel, e);
+        }
+    });
+}
+
+/// Describes why the server refused to let us join the channel.
+pub fn join_error_reason(response: Response) -> &'static str {
+    match response {
+        Response::ERR_BANNEDFROMCHAN => "the bot is banned from the channel",
+        Response::ERR_CHANNELISFULL => "the channel is full",
+        Response::ERR_INVITEONLYCHAN => "the channel is invite only",
+        Response::ERR_BADCHANNELKEY => "the channel key is wrong",
+        _ => "the server refused it",
+    }
+}
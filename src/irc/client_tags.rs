@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serenity::model::channel::ReactionType;
+use serenity::model::id::ChannelId;
+
+use crate::config::TypingConfig;
+use crate::format::irc_msg_to_discord;
+use crate::state::State;
+use crate::store::Origin;
+use crate::utils::excerpt;
+
+/// Number of characters of the original message quoted with a reply.
+const QUOTE_EXCERPT_LEN: usize = 60;
+
+/// Renders the message a `+draft/reply` tag refers to as a Discord quote, like
+/// `> **alice**: original text…`, if it was bridged.
+pub fn quote(state: &State, irc_msgid: &str) -> Result<Option<String>> {
+    let Some(original) = state.store.by_irc_msgid(irc_msgid)? else {
+        return Ok(None);
+    };
+    let content = match original.origin {
+        Origin::Irc => irc_msg_to_discord(&original.content),
+        Origin::Discord => original.content,
+    };
+    Ok(Some(format!(
+        "> **{}**: {}\n",
+        original.author,
+        excerpt(&content, QUOTE_EXCERPT_LEN)
+    )))
+}
+
+/// Adds the reaction of a `+draft/react` tag to the message it refers to in the bridged channel.
+/// Reactions Discord does not accept, like text reactions, are dropped.
+pub async fn react(
+    discord: &serenity::CacheAndHttp,
+    state: &State,
+    channel_id: u64,
+    nickname: &str,
+    irc_msgid: &str,
+    emoji: &str,
+) -> Result<()> {
+    let Some(original) = state.store.by_irc_msgid(irc_msgid)? else {
+        debug!("IRC| {} reacted {} to an unknown message", nickname, emoji);
+        return Ok(());
+    };
+    info!(
+        "IRC> {} reacted {} to {}",
+        nickname, emoji, original.discord_id
+    );
+    let reaction = ReactionType::Unicode(emoji.to_string());
+    if let Err(e) = discord
+        .http
+        .create_reaction(channel_id, original.discord_id, &reaction)
+        .await
+    {
+        warn!("Failed to add reaction {} of {}: {}", emoji, nickname, e);
+    }
+    Ok(())
+}
+
+/// Shows on Discord that someone is typing on IRC, unless it was shown recently.
+pub async fn typing(
+    discord: &serenity::CacheAndHttp,
+    config: &TypingConfig,
+    state: &State,
+    channel_id: u64,
+) {
+    if !state.typing_on_discord(Duration::from_secs(config.discord_interval)) {
+        return;
+    }
+    if let Err(e) = ChannelId(channel_id).broadcast_typing(&discord.http).await {
+        warn!("Failed to show typing on Discord: {}", e);
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use serenity::{builder::EditWebhookMessage, json::hashmap_to_json_map};
+
+use crate::config::CorrectionsConfig;
+use crate::format::{Substitution, irc_msg_to_discord};
+use crate::state::State;
+use crate::store::Origin;
+
+/// Applies a `s/old/new/` line to the last message of `nickname`, editing it on Discord. Returns
+/// whether the message was edited; otherwise the line should be posted as usual.
+pub async fn try_correct(
+    discord: &serenity::CacheAndHttp,
+    config: &CorrectionsConfig,
+    state: &State,
+    (webhook_id, webhook_token): (u64, &str),
+    nickname: &str,
+    line: &str,
+) -> Result<bool> {
+    let Some(substitution) = Substitution::parse(line) else {
+        return Ok(false);
+    };
+    let Some(last) = state.store.last_from(Origin::Irc, nickname)? else {
+        return Ok(false);
+    };
+    if last.age() > config.window {
+        debug!("IRC| <{}> correction of a message too old", nickname);
+        return Ok(false);
+    }
+    let Some(corrected) = substitution.apply(&last.content) else {
+        return Ok(false);
+    };
+
+    let mut builder = EditWebhookMessage::default();
+    builder.content(irc_msg_to_discord(&corrected));
+    let json = hashmap_to_json_map(builder.0);
+    if let Err(e) = discord
+        .http
+        .edit_webhook_message(webhook_id, webhook_token, last.discord_id, &json)
+        .await
+    {
+        // The message was deleted, or cannot be edited anymore.
+        warn!("Failed to edit message {}: {}", last.discord_id, e);
+        return Ok(false);
+    }
+
+    state.store.set_content(last.discord_id, &corrected)?;
+    Ok(true)
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use serenity::model::channel::ReactionType;
+use serenity::model::id::{ChannelId, MessageId};
+
+use crate::config::IrcConfig;
+use crate::state::{PendingDelivery, State};
+
+/// Called when the server echoes a line we sent to `target` back to us, with the ID the server
+/// gave to it.
+pub async fn report_success(
+    discord: &serenity::CacheAndHttp,
+    config: &IrcConfig,
+    state: &State,
+    target: &str,
+    irc_msgid: Option<&str>,
+) {
+    if let Some(delivery) = state.line_delivered(target, irc_msgid) {
+        report(discord, config, state, delivery).await;
+    }
+}
+
+/// Called when the server refuses a line we sent to `target`.
+pub async fn report_failure(
+    discord: &serenity::CacheAndHttp,
+    config: &IrcConfig,
+    state: &State,
+    target: &str,
+    reason: &str,
+) {
+    warn!("IRC| Cannot send to {}: {}", target, reason);
+    if let Some(delivery) = state.line_refused(target, reason) {
+        report(discord, config, state, delivery).await;
+    }
+}
+
+/// Remembers the IRC ID of a Discord message whose lines were all answered by the server, and
+/// reports it. Discord errors, like a deleted message or a missing permission, are only logged.
+async fn report(
+    discord: &serenity::CacheAndHttp,
+    config: &IrcConfig,
+    state: &State,
+    delivery: PendingDelivery,
+) {
+    let Some((channel_id, message_id)) = delivery.message else {
+        return;
+    };
+    if let Some(irc_msgid) = &delivery.irc_msgid
+        && let Err(e) = state.store.set_irc_msgid(message_id, irc_msgid)
+    {
+        warn!("Failed to store message {}: {}", message_id, e);
+    }
+    let Some(reports) = &config.delivery_reports else {
+        return;
+    };
+
+    let reaction = match &delivery.refusal {
+        None => reports.success_reaction.as_deref(),
+        Some(reason) => {
+            warn!(
+                "IRC| Cannot send message {} to {}: {}",
+                message_id, delivery.irc_channel, reason
+            );
+            Some(reports.failure_reaction.as_str())
+        }
+    };
+    if let Some(reaction) = reaction {
+        match ReactionType::try_from(reaction) {
+            Ok(reaction) => {
+                if let Err(e) = discord
+                    .http
+                    .create_reaction(channel_id, message_id, &reaction)
+                    .await
+                {
+                    warn!("Failed to react to message {}: {}", message_id, e);
+                }
+            }
+            Err(e) => warn!("Invalid delivery report reaction {}: {}", reaction, e),
+        }
+    }
+
+    if let Some(reason) = &delivery.refusal
+        && reports.explain
+    {
+        let channel_id = ChannelId(channel_id);
+        let reply = channel_id
+            .send_message(&discord.http, |m| {
+                m.content(format_args!("IRC did not accept this message: {}", reason))
+                    .reference_message((channel_id, MessageId(message_id)))
+                    .allowed_mentions(|am| am.replied_user(false))
+            })
+            .await;
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                warn!("Failed to explain a delivery failure: {}", e);
+                return;
+            }
+        };
+
+        // Discord has no ephemeral replies outside of interactions, so remove it after a while.
+        let http = discord.http.clone();
+        let lifetime = Duration::from_secs(reports.explanation_lifetime);
+        tokio::spawn(async move {
+            tokio::time::sleep(lifetime).await;
+            if let Err(e) = reply.delete(&http).await {
+                warn!("Failed to delete delivery report: {}", e);
+            }
+        });
+    }
+}
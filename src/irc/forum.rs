@@ -0,0 +1,46 @@
+use anyhow::Result;
+use libirc::client::Sender;
+use serenity::model::id::ChannelId;
+
+use crate::format::irc_msg_to_discord;
+use crate::state::State;
+use crate::store::Origin;
+
+/// Handles a message in the IRC channel of the forum. Only `!reply <post> <text>` is relayed, as
+/// lines cannot be told apart otherwise.
+pub async fn handle(
+    discord: &serenity::CacheAndHttp,
+    irc_sender: &Sender,
+    state: &State,
+    nickname: &str,
+    content: &str,
+) -> Result<()> {
+    let Some(args) = content.strip_prefix("!reply ") else {
+        debug!("IRC| [forum] <{}> {}", nickname, content);
+        return Ok(());
+    };
+    let Some((number, text)) = args.trim_start().split_once(' ') else {
+        irc_sender.send_notice(nickname, "Usage: !reply <post number> <text>")?;
+        return Ok(());
+    };
+    let thread_id = match number.trim_start_matches('#').parse() {
+        Ok(number) => state.store.forum_post(number)?,
+        Err(_) => None,
+    };
+    let Some(thread_id) = thread_id else {
+        irc_sender.send_notice(nickname, format!("There is no post {}.", number))?;
+        return Ok(());
+    };
+
+    info!("IRC> [forum {}] <{}> {}", number, nickname, text);
+    ChannelId(thread_id)
+        .say(
+            &discord.http,
+            format_args!("**<{}>** {}", nickname, irc_msg_to_discord(text.trim())),
+        )
+        .await?;
+    if let Some(loops) = &state.loops {
+        loops.record(Origin::Irc, text);
+    }
+    Ok(())
+}
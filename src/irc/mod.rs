@@ -0,0 +1,630 @@
+mod cap;
+mod channel;
+mod client_tags;
+mod correction;
+mod delivery;
+mod forum;
+mod modes;
+mod names;
+mod private;
+mod services;
+mod topic;
+mod whois;
+
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use libirc::client::Sender;
+use libirc::client::prelude::{ChannelExt, Command, Message, Prefix, Response};
+use libirc::proto::message::Tag;
+use serenity::{builder::ExecuteWebhook, json::hashmap_to_json_map};
+
+use crate::admin;
+use crate::casemapping::CaseMapping;
+use crate::config::{DiscordConfig, IgnoreDirection, IrcConfig};
+use crate::format::irc_msg_to_discord;
+use crate::ignore::Source;
+use crate::loops::Author;
+use crate::state::{SaslStatus, State};
+use crate::store::{MessageRecord, Origin, now};
+use crate::utils::mask_matches;
+
+pub use cap::identify;
+
+pub async fn handle_irc(
+    msg: Message,
+    irc_sender: Sender,
+    discord: &serenity::CacheAndHttp,
+    config: IrcConfig,
+    discord_config: DiscordConfig,
+    state: &Arc<State>,
+) -> Result<()> {
+    let DiscordConfig {
+        channel_id,
+        webhook_id,
+        webhook_token,
+        private_messages,
+        typing,
+        forum,
+        topic: topic_config,
+        names: names_config,
+        ..
+    } = discord_config;
+    match msg.command {
+        Command::ERROR(args) => error!("IRC> Error {}", args),
+        Command::CAP(_, subcommand, arg, last_arg) => {
+            cap::handle_cap(&irc_sender, &config, state, subcommand, arg, last_arg)?;
+        }
+        Command::AUTHENTICATE(data) => {
+            cap::handle_authenticate(&irc_sender, &config, state, &data)?;
+        }
+        Command::Response(Response::RPL_SASLSUCCESS, _) => {
+            cap::finish_sasl(&irc_sender, state, Ok(()))?;
+        }
+        Command::Response(
+            Response::ERR_NICKLOCKED
+            | Response::ERR_SASLFAIL
+            | Response::ERR_SASLTOOLONG
+            | Response::ERR_SASLABORT
+            | Response::ERR_SASLALREADY,
+            args,
+        ) => {
+            let reason = args.last().cloned().unwrap_or_default();
+            cap::finish_sasl(&irc_sender, state, Err(reason))?;
+        }
+        Command::Response(Response::RPL_WELCOME, args) => {
+            if config.sasl.is_some() {
+                match &state.irc().sasl {
+                    SaslStatus::Succeeded => {}
+                    SaslStatus::Failed(reason) => {
+                        bail!("SASL authentication failed: {}", reason)
+                    }
+                    _ => bail!("SASL authentication did not complete before registration"),
+                }
+            }
+
+            {
+                // A new registration starts outside of the channel.
+                let mut session = state.irc();
+                session.nickname = args.into_iter().next();
+                session.joined = false;
+                session.join_sent = false;
+            }
+
+            if let Some(ozinger) = &config.ozinger {
+                irc_sender.send_oper(&ozinger.username, &ozinger.password)?;
+            }
+
+            if services::identify(&irc_sender, &config, state)? {
+                channel::join(&irc_sender, &config, state)?;
+            }
+        }
+        Command::Response(Response::RPL_LOGGEDIN, args) => {
+            info!(
+                "IRC| {}",
+                args.last().map(String::as_str).unwrap_or("Logged in")
+            );
+            let should_join = {
+                let mut session = state.irc();
+                session.logged_in = true;
+                session.nickname.is_some() && !session.join_sent
+            };
+            if should_join {
+                channel::join(&irc_sender, &config, state)?;
+            }
+        }
+        Command::Response(Response::RPL_LOGGEDOUT, _) => {
+            state.irc().logged_in = false;
+        }
+        Command::Response(Response::RPL_ISON, args) => {
+            services::ison(&irc_sender, &config, state, &args)?;
+        }
+        Command::NICK(new_nickname) => {
+            if let Some(Prefix::Nickname(nickname, ..)) = msg.prefix {
+                state.roster().rename(&nickname, &new_nickname);
+                let mut session = state.irc();
+                if session.is_me(&nickname) {
+                    info!(
+                        "IRC| Nickname changed from {} to {}",
+                        nickname, new_nickname
+                    );
+                    session.nickname = Some(new_nickname);
+                }
+            }
+        }
+        Command::PRIVMSG(target, content) => {
+            if let Some(Prefix::Nickname(nickname, username, hostname)) = msg.prefix {
+                let hostmask = format!("{}!{}@{}", nickname, username, hostname);
+                let account = tag(&msg.tags, "account");
+                if state.irc().is_me(&nickname) {
+                    // Echo of a line we sent, with the `echo-message` capability.
+                    let irc_msgid = tag(&msg.tags, "msgid");
+                    delivery::report_success(discord, &config, state, &target, irc_msgid).await;
+                } else if let Some(args) = admin::parse(&content)
+                    && is_admin(&config, &hostmask, account)
+                {
+                    let answer = admin::run(state, &irc_sender, &nickname, args)
+                        .unwrap_or_else(|e| format!("Failed: {}", e));
+                    irc_sender.send_notice(&nickname, answer)?;
+                } else if is_ignored(state, &nickname, &hostmask, account, &content) {
+                    debug!("IRC| <{}(ignored)> {}", nickname, content);
+                } else if state.settings().paused {
+                    debug!("IRC| <{}(paused)> {}", nickname, content);
+                } else if !target.is_channel_name() {
+                    match &private_messages {
+                        Some(private_messages) if !content.starts_with('\u{1}') => {
+                            info!("IRC> [PM] <{}> {}", nickname, content);
+                            private::relay(discord, private_messages, state, &nickname, &content)
+                                .await?;
+                        }
+                        _ => debug!("IRC| [PM] <{}> {}", nickname, content),
+                    }
+                } else if let Some(forum_config) = &forum
+                    && target.eq_ignore_ascii_case(&forum_config.irc_channel)
+                {
+                    forum::handle(discord, &irc_sender, state, &nickname, &content).await?;
+                } else if !target.eq_ignore_ascii_case(&config.channel) {
+                    // Channels of Discord threads.
+                    match state.thread_of_channel(&target) {
+                        Some(_) if is_echo(state, &nickname, &content) => {}
+                        Some(thread_id) => {
+                            info!("IRC> [{}] <{}> {}", target, nickname, content);
+                            serenity::model::id::ChannelId(thread_id)
+                                .say(
+                                    &discord.http,
+                                    format_args!(
+                                        "**<{}>** {}",
+                                        nickname,
+                                        irc_msg_to_discord(&content)
+                                    ),
+                                )
+                                .await?;
+                            if let Some(loops) = &state.loops {
+                                loops.record(Origin::Irc, &content);
+                            }
+                        }
+                        None => debug!("IRC| [{}] <{}> {}", target, nickname, content),
+                    }
+                } else if is_echo(state, &nickname, &content) {
+                } else {
+                    info!("IRC> <{}> {}", nickname, content);
+
+                    if names_config.is_some() && content.trim() == "!names" {
+                        names::answer(&irc_sender, &discord.cache, channel_id, &nickname)?;
+                    }
+
+                    if let Some(corrections) = &config.corrections
+                        && correction::try_correct(
+                            discord,
+                            corrections,
+                            state,
+                            (webhook_id, &webhook_token),
+                            &nickname,
+                            &content,
+                        )
+                        .await?
+                    {
+                        return Ok(());
+                    }
+
+                    let mut avatar = None;
+                    if config.auto_detect_avatar {
+                        avatar = auto_detect_avatar(&discord.cache, channel_id, &nickname).await;
+                    }
+
+                    let mut discord_content = irc_msg_to_discord(&content);
+                    if let Some(reply_to) = tag(&msg.tags, "+draft/reply")
+                        && let Some(quote) = client_tags::quote(state, reply_to)?
+                    {
+                        discord_content.insert_str(0, &quote);
+                    }
+
+                    let username = modes::decorate(&config, state, &nickname);
+                    let mut builder = ExecuteWebhook::default();
+                    builder.username(username).content(discord_content);
+                    if let Some(avatar) = avatar {
+                        builder.avatar_url(avatar);
+                    }
+                    let json = hashmap_to_json_map(builder.0);
+                    let message = discord
+                        .http
+                        .execute_webhook(webhook_id, &webhook_token, true, &json)
+                        .await?;
+                    if let Some(loops) = &state.loops {
+                        loops.record(Origin::Irc, &content);
+                    }
+                    if let Some(message) = message {
+                        state.store.insert(&MessageRecord {
+                            discord_id: message.id.0,
+                            irc_msgid: tag(&msg.tags, "msgid").map(Into::into),
+                            origin: Origin::Irc,
+                            author_id: None,
+                            author: nickname,
+                            content,
+                            timestamp: now(),
+                        })?;
+                    }
+                }
+            }
+        }
+        Command::NOTICE(target, _) if is_from_me(state, &msg.prefix) => {
+            // Echo of a notice we sent.
+            delivery::report_success(discord, &config, state, &target, None).await;
+        }
+        Command::Raw(ref command, ref args)
+            if command == "TAGMSG" && is_from_me(state, &msg.prefix) =>
+        {
+            // Echo of a reaction or typing notification we sent.
+            if let Some(target) = args.first() {
+                delivery::report_success(discord, &config, state, target, None).await;
+            }
+        }
+        Command::Raw(command, args) if command == "TAGMSG" => {
+            if let Some(Prefix::Nickname(nickname, _, _)) = msg.prefix
+                // Reactions and typing are only bridged for the bridged channel, whose messages
+                // are stored.
+                && args
+                    .first()
+                    .is_some_and(|target| target.eq_ignore_ascii_case(&config.channel))
+                && !state.irc().is_me(&nickname)
+                && !state.is_irc_ignored(&nickname)
+                && !state.settings().paused
+            {
+                if let Some(reply_to) = tag(&msg.tags, "+draft/reply")
+                    && let Some(emoji) = tag(&msg.tags, "+draft/react")
+                {
+                    client_tags::react(discord, state, channel_id, &nickname, reply_to, emoji)
+                        .await?;
+                }
+                if let Some(typing_config) = &typing
+                    && tag(&msg.tags, "+typing") == Some("active")
+                {
+                    client_tags::typing(discord, typing_config, state, channel_id).await;
+                }
+            }
+        }
+        Command::TOPIC(target, Some(topic)) if target.eq_ignore_ascii_case(&config.channel) => {
+            match (&topic_config, msg.prefix) {
+                (Some(topic_config), Some(Prefix::Nickname(nickname, ..)))
+                    if !state.irc().is_me(&nickname) =>
+                {
+                    topic::changed(discord, topic_config, state, channel_id, &nickname, topic)
+                        .await?;
+                }
+                // Our own change, or synchronization is disabled.
+                _ => state.topics().irc = Some(topic),
+            }
+        }
+        Command::Response(Response::RPL_TOPIC, args)
+            if args
+                .get(1)
+                .is_some_and(|c| c.eq_ignore_ascii_case(&config.channel)) =>
+        {
+            // The topic when joining is only recorded, to not overwrite the Discord one.
+            state.topics().irc = args.into_iter().nth(2);
+        }
+        Command::Response(Response::RPL_NOTOPIC, args)
+            if args
+                .get(1)
+                .is_some_and(|c| c.eq_ignore_ascii_case(&config.channel)) =>
+        {
+            state.topics().irc = Some(String::new());
+        }
+        Command::JOIN(ref channel, ..)
+            if !channel.eq_ignore_ascii_case(&config.channel) && is_from_me(state, &msg.prefix) =>
+        {
+            // The channel of a thread, whose first lines waited for the bot to join it.
+            for command in state.finish_joining(channel) {
+                irc_sender.send(command)?;
+            }
+        }
+        Command::JOIN(ref channel, ..)
+        | Command::PART(ref channel, _)
+        | Command::KICK(ref channel, ..)
+            if !channel.eq_ignore_ascii_case(&config.channel) =>
+        {
+            // Channels of Discord threads.
+            debug!("IRC> {:?}", msg);
+        }
+        Command::JOIN(..) => {
+            if let Some(Prefix::Nickname(nickname, ..)) = msg.prefix {
+                if state.irc().is_me(&nickname) {
+                    info!("IRC| Joined {}", config.channel);
+                    state.irc().joined = true;
+                    // The server sends the members with RPL_NAMREPLY, and WHO tells who is away.
+                    state.roster().clear();
+                    irc_sender.send(Command::WHO(Some(config.channel.clone()), None))?;
+                    // Join the channel of the forum once identified, like the bridged one.
+                    if let Some(forum) = &forum {
+                        irc_sender.send_join(&forum.irc_channel)?;
+                    }
+                } else {
+                    state.roster().join(&nickname);
+                }
+                if state.settings().bridge_member_changes
+                    && !state.irc().is_me(&nickname)
+                    && !state.is_irc_ignored(&nickname)
+                {
+                    serenity::model::id::ChannelId::from(channel_id)
+                        .say(
+                            &discord.http,
+                            format_args!("**{}** has joined the channel.", nickname),
+                        )
+                        .await?;
+                }
+            }
+        }
+        Command::PART(_, comment) | Command::QUIT(comment) => {
+            // PARTs of other channels are handled above. QUIT is sent for every channel we share
+            // with the user, so it is only announced if they were in the bridged channel.
+            if let Some(Prefix::Nickname(nickname, ..)) = msg.prefix
+                && state.roster().part(&nickname)
+                && state.settings().bridge_member_changes
+                && !state.irc().is_me(&nickname)
+                && !state.is_irc_ignored(&nickname)
+            {
+                let mut message = format!("**{}** has left the channel.", nickname);
+                if let Some(comment) = comment {
+                    message.push_str(" (`");
+                    message.push_str(&comment);
+                    message.push_str("`)");
+                }
+                serenity::model::id::ChannelId::from(channel_id)
+                    .say(&discord.http, message)
+                    .await?;
+            }
+        }
+        Command::KICK(_, nickname, comment) if state.irc().is_me(&nickname) => {
+            state.irc().joined = false;
+            state.roster().clear();
+            let mut message = format!("The bot has been kicked from **{}**", config.channel);
+            if let Some(Prefix::Nickname(kicked_by, ..)) = msg.prefix {
+                message.push_str(" by **");
+                message.push_str(&kicked_by);
+                message.push_str("**");
+            }
+            message.push('.');
+            if let Some(comment) = comment {
+                message.push_str(" (`");
+                message.push_str(&comment);
+                message.push_str("`)");
+            }
+            warn!("IRC| {}", message);
+            serenity::model::id::ChannelId::from(channel_id)
+                .say(&discord.http, message)
+                .await?;
+            channel::schedule_rejoin(&irc_sender, &config, state);
+        }
+        Command::KICK(_, nickname, comment) => {
+            state.roster().part(&nickname);
+            if let Some(Prefix::Nickname(kicked_by, ..)) = msg.prefix
+                && state.settings().bridge_member_changes
+                && !state.irc().is_me(&nickname)
+                && !state.is_irc_ignored(&nickname)
+            {
+                let mut message = format!("**{}** has been kicked by **{}**.", nickname, kicked_by);
+                if let Some(comment) = comment {
+                    message.push_str(" (`");
+                    message.push_str(&comment);
+                    message.push_str("`)");
+                }
+                serenity::model::id::ChannelId::from(channel_id)
+                    .say(&discord.http, message)
+                    .await?;
+            }
+        }
+        Command::Response(Response::RPL_ISUPPORT, args) => {
+            if let Some(token) = args.iter().find_map(|arg| arg.strip_prefix("PREFIX=")) {
+                state.roster().set_prefixes(token);
+            }
+            if let Some(token) = args.iter().find_map(|arg| arg.strip_prefix("CASEMAPPING=")) {
+                let casemapping = CaseMapping::parse(token);
+                state.irc().casemapping = casemapping;
+                state.roster().set_casemapping(casemapping);
+            }
+        }
+        Command::Response(Response::RPL_NAMREPLY, args) => {
+            if let [_, _, channel, names, ..] = args.as_slice()
+                && channel.eq_ignore_ascii_case(&config.channel)
+            {
+                state.roster().add_names(names);
+            }
+        }
+        Command::Response(
+            response @ (Response::RPL_WHOISUSER
+            | Response::RPL_WHOISSERVER
+            | Response::RPL_WHOISOPERATOR
+            | Response::RPL_AWAY
+            | Response::RPL_WHOISIDLE
+            | Response::RPL_WHOISCHANNELS
+            | Response::RPL_ENDOFWHOIS
+            | Response::ERR_NOSUCHNICK),
+            args,
+        ) => whois::reply(state, response, &args),
+        Command::Raw(command, args) if command == "330" => whois::account(state, &args),
+        Command::Response(Response::RPL_WHOREPLY, args) => {
+            if let [_, channel, _, _, _, nickname, flags, ..] = args.as_slice()
+                && channel.eq_ignore_ascii_case(&config.channel)
+            {
+                state.roster().set_away(nickname, flags.starts_with('G'));
+            }
+        }
+        Command::AWAY(message) => {
+            if let Some(Prefix::Nickname(nickname, ..)) = msg.prefix {
+                state.roster().set_away(&nickname, message.is_some());
+            }
+        }
+        Command::ChannelMODE(target, modes) if target.eq_ignore_ascii_case(&config.channel) => {
+            let changed_by = match msg.prefix {
+                Some(Prefix::Nickname(nickname, ..)) => nickname,
+                Some(Prefix::ServerName(name)) => name,
+                None => String::new(),
+            };
+            modes::changed(discord, &config, state, channel_id, &changed_by, &modes).await?;
+        }
+        Command::Response(Response::ERR_CANNOTSENDTOCHAN, args) => {
+            if let [_, target, reason, ..] = args.as_slice() {
+                delivery::report_failure(discord, &config, state, target, reason).await;
+            }
+        }
+        Command::INVITE(_, invited_to) => {
+            if config.join_on_invite
+                && invited_to.eq_ignore_ascii_case(&config.channel)
+                && !state.irc().joined
+            {
+                info!("IRC| Invited to {}, joining", invited_to);
+                channel::join(&irc_sender, &config, state)?;
+            }
+        }
+        Command::Response(
+            response @ (Response::ERR_BANNEDFROMCHAN
+            | Response::ERR_CHANNELISFULL
+            | Response::ERR_INVITEONLYCHAN
+            | Response::ERR_BADCHANNELKEY),
+            args,
+        ) if args
+            .get(1)
+            .is_some_and(|channel| channel.eq_ignore_ascii_case(&config.channel)) =>
+        {
+            let reason = channel::join_error_reason(response);
+            let mut message = format!("Cannot join **{}** on IRC: {}.", config.channel, reason);
+            if let Some(text) = args.last() {
+                message.push_str(" (`");
+                message.push_str(text);
+                message.push_str("`)");
+            }
+            warn!("IRC| {}", message);
+            serenity::model::id::ChannelId::from(channel_id)
+                .say(&discord.http, message)
+                .await?;
+            channel::schedule_rejoin(&irc_sender, &config, state);
+        }
+        Command::Response(
+            response @ (Response::ERR_BANNEDFROMCHAN
+            | Response::ERR_CHANNELISFULL
+            | Response::ERR_INVITEONLYCHAN
+            | Response::ERR_BADCHANNELKEY),
+            args,
+        ) => {
+            // Channels of Discord threads or of the forum.
+            warn!(
+                "IRC| Cannot join {}: {}",
+                args.get(1).map_or("a channel", String::as_str),
+                channel::join_error_reason(response)
+            );
+            if let Some(channel) = args.get(1) {
+                let dropped = state.finish_joining(channel).len();
+                if dropped > 0 {
+                    warn!("IRC| Dropped {} lines sent to {}", dropped, channel);
+                }
+                // Join again with the next message of the thread.
+                state
+                    .thread_channels()
+                    .retain(|_, thread_channel| !thread_channel.eq_ignore_ascii_case(channel));
+            }
+        }
+        _ => {
+            debug!("IRC> {:?}", msg);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the IRC user with `hostmask`, logged in to `account`, may use admin commands.
+fn is_admin(config: &IrcConfig, hostmask: &str, account: Option<&str>) -> bool {
+    let Some(admins) = &config.admins else {
+        return false;
+    };
+    admins
+        .hostmasks
+        .iter()
+        .any(|mask| mask_matches(mask, hostmask))
+        || account.is_some_and(|account| admins.accounts.iter().any(|a| a == account))
+}
+
+/// Returns whether the message of an IRC user must not be relayed.
+fn is_ignored(
+    state: &State,
+    nickname: &str,
+    hostmask: &str,
+    account: Option<&str>,
+    content: &str,
+) -> bool {
+    if state.is_irc_ignored(nickname) {
+        return true;
+    }
+    let source = Source {
+        direction: IgnoreDirection::IrcToDiscord,
+        nickname,
+        casemapping: state.irc().casemapping,
+        hostmask: Some(hostmask),
+        account,
+        content,
+        ..Default::default()
+    };
+    match state.ignore_rules.matching(&source) {
+        Some(rule) => {
+            debug!(
+                "IRC| Message of {} matched the ignore rule {}",
+                nickname, rule
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns whether a message was sent by the bot, and echoed back with the `echo-message`
+/// capability.
+fn is_from_me(state: &State, prefix: &Option<Prefix>) -> bool {
+    matches!(prefix, Some(Prefix::Nickname(nickname, ..)) if state.irc().is_me(nickname))
+}
+
+/// Returns whether the message of an IRC user is the echo of a message relayed from IRC, sent
+/// back by another bridge.
+fn is_echo(state: &State, nickname: &str, content: &str) -> bool {
+    let Some(loops) = &state.loops else {
+        return false;
+    };
+    let casemapping = state.irc().casemapping;
+    match loops.echo(Origin::Irc, Author::Irc(nickname, casemapping), content) {
+        Some(bridge) => {
+            debug!("IRC| <{}(echo from {})> {}", nickname, bridge, content);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the value of an IRCv3 message tag.
+fn tag<'a>(tags: &'a Option<Vec<Tag>>, name: &str) -> Option<&'a str> {
+    tags.as_ref()?
+        .iter()
+        .find(|Tag(key, _)| key == name)
+        .and_then(|Tag(_, value)| value.as_deref())
+}
+
+async fn auto_detect_avatar(
+    cache: &serenity::cache::Cache,
+    channel_id: u64,
+    nickname: &str,
+) -> Option<String> {
+    match cache.guild_channel(channel_id) {
+        None => {}
+        Some(channel) => match channel.members(&cache).await {
+            Err(_) => {}
+            Ok(members) => {
+                for member in members {
+                    if *member.display_name() == nickname {
+                        return Some(member.face());
+                    }
+                }
+                return None;
+            }
+        },
+    };
+
+    warn!("Cache missed while it should never be missed");
+    None
+}
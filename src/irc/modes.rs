@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+use libirc::client::prelude::{ChannelMode, Mode};
+use serenity::model::id::ChannelId;
+
+use crate::config::{IrcConfig, PrivilegeDisplay};
+use crate::state::State;
+
+/// Applies channel mode changes to the roster and announces the configured ones on Discord.
+pub async fn changed(
+    discord: &serenity::CacheAndHttp,
+    config: &IrcConfig,
+    state: &State,
+    channel_id: u64,
+    changed_by: &str,
+    modes: &[Mode<ChannelMode>],
+) -> Result<()> {
+    let mut announcements = Vec::new();
+    for mode in modes {
+        let (letter, enabled, arg) = match mode {
+            Mode::Plus(mode, arg) => (mode.to_string(), true, arg),
+            Mode::Minus(mode, arg) => (mode.to_string(), false, arg),
+            Mode::NoPrefix(_) => continue,
+        };
+        let Some(letter) = letter.chars().next() else {
+            continue;
+        };
+        if let Some(nickname) = arg {
+            state.roster().set_mode(nickname, letter, enabled);
+        }
+        if let Some(modes_config) = &config.modes
+            && modes_config.announce.contains(letter)
+        {
+            announcements.push(describe(letter, enabled, arg.as_deref()));
+        }
+    }
+    if announcements.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!("**{}** {}.", changed_by, announcements.join(", "));
+    info!("IRC> {}", message);
+    ChannelId(channel_id).say(&discord.http, message).await?;
+    Ok(())
+}
+
+fn describe(mode: char, enabled: bool, arg: Option<&str>) -> String {
+    let privilege = match mode {
+        'q' => Some("owner status"),
+        'a' => Some("admin status"),
+        'o' => Some("operator status"),
+        'h' => Some("half-operator status"),
+        'v' => Some("voice"),
+        _ => None,
+    };
+    match (privilege, mode, enabled, arg) {
+        (Some(privilege), _, true, Some(nickname)) => {
+            format!("gave {} to **{}**", privilege, nickname)
+        }
+        (Some(privilege), _, false, Some(nickname)) => {
+            format!("took {} from **{}**", privilege, nickname)
+        }
+        (_, 'b', true, Some(mask)) => format!("banned `{}`", mask),
+        (_, 'b', false, Some(mask)) => format!("unbanned `{}`", mask),
+        (_, _, enabled, arg) => {
+            let sign = if enabled { '+' } else { '-' };
+            match arg {
+                Some(arg) => format!("set mode {}{} `{}`", sign, mode, arg),
+                None => format!("set mode {}{}", sign, mode),
+            }
+        }
+    }
+}
+
+/// Returns the Discord name of an IRC user, with their privilege in the channel if configured.
+pub fn decorate<'a>(config: &IrcConfig, state: &State, nickname: &'a str) -> Cow<'a, str> {
+    let display = config.modes.as_ref().map(|modes| modes.privilege);
+    let prefix = match display {
+        Some(PrivilegeDisplay::Prefix | PrivilegeDisplay::Suffix) => {
+            state.roster().prefix(nickname)
+        }
+        _ => None,
+    };
+    match (display, prefix) {
+        (Some(PrivilegeDisplay::Prefix), Some(prefix)) => format!("{}{}", prefix, nickname).into(),
+        (Some(PrivilegeDisplay::Suffix), Some(prefix)) => {
+            format!("{} ({})", nickname, prefix).into()
+        }
+        _ => nickname.into(),
+    }
+}
+
+#[test]
+fn test_describe() {
+    assert_eq!(
+        describe('o', true, Some("bob")),
+        "gave operator status to **bob**"
+    );
+    assert_eq!(describe('v', false, Some("bob")), "took voice from **bob**");
+    assert_eq!(describe('b', true, Some("*!*@spam")), "banned `*!*@spam`");
+    assert_eq!(describe('m', true, None), "set mode +m");
+    assert_eq!(describe('l', true, Some("10")), "set mode +l `10`");
+}
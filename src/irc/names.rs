@@ -0,0 +1,59 @@
+use anyhow::Result;
+use libirc::client::Sender;
+use serenity::cache::Cache;
+use serenity::model::user::OnlineStatus;
+
+/// Maximum length of a line listing Discord members.
+const LINE_LEN: usize = 350;
+
+/// Answers `!names` with the online Discord members who can see the bridged channel, in notices
+/// to `nickname`.
+pub fn answer(irc_sender: &Sender, cache: &Cache, channel_id: u64, nickname: &str) -> Result<()> {
+    let Some(channel) = cache.guild_channel(channel_id) else {
+        return Ok(());
+    };
+    let Some(guild) = cache.guild(channel.guild_id) else {
+        return Ok(());
+    };
+    let mut names: Vec<_> = guild
+        .members
+        .values()
+        .filter(|member| !member.user.bot)
+        .filter(|member| {
+            guild
+                .presences
+                .get(&member.user.id)
+                .is_some_and(|presence| presence.status != OnlineStatus::Offline)
+        })
+        .filter(|member| {
+            guild
+                .user_permissions_in(&channel, member)
+                .is_ok_and(|permissions| permissions.view_channel())
+        })
+        .map(|member| member.display_name().into_owned())
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+
+    irc_sender.send_notice(
+        nickname,
+        format!(
+            "Online on Discord in #{}: {} users",
+            channel.name,
+            names.len()
+        ),
+    )?;
+    let mut line = String::new();
+    for name in names {
+        if !line.is_empty() && line.len() + name.len() + 2 > LINE_LEN {
+            irc_sender.send_notice(nickname, std::mem::take(&mut line))?;
+        }
+        if !line.is_empty() {
+            line.push_str(", ");
+        }
+        line.push_str(&name);
+    }
+    if !line.is_empty() {
+        irc_sender.send_notice(nickname, line)?;
+    }
+    Ok(())
+}
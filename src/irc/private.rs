@@ -0,0 +1,87 @@
+use anyhow::Result;
+use serenity::model::channel::ChannelType;
+use serenity::model::id::ChannelId;
+
+use crate::config::PrivateMessagesConfig;
+use crate::format::irc_msg_to_discord;
+use crate::state::State;
+
+/// Relays a private message to the bot into the configured Discord channel, or into the thread of
+/// the sender.
+pub async fn relay(
+    discord: &serenity::CacheAndHttp,
+    config: &PrivateMessagesConfig,
+    state: &State,
+    nickname: &str,
+    content: &str,
+) -> Result<()> {
+    let content = irc_msg_to_discord(content);
+    let channel_id = if config.use_threads {
+        thread_of(discord, config, state, nickname).await?
+    } else {
+        ChannelId(config.channel_id)
+    };
+
+    let message = channel_id
+        .say(
+            &discord.http,
+            format_args!("**<{}>** {}", nickname, content),
+        )
+        .await?;
+    state
+        .private_chats()
+        .push_message(message.id.0, nickname.to_string());
+    Ok(())
+}
+
+/// Finds the thread for private messages of `nickname`, creating one if there is none.
+async fn thread_of(
+    discord: &serenity::CacheAndHttp,
+    config: &PrivateMessagesConfig,
+    state: &State,
+    nickname: &str,
+) -> Result<ChannelId> {
+    let casemapping = state.irc().casemapping;
+    let known = state
+        .private_chats()
+        .threads
+        .iter()
+        .find(|(known, _)| casemapping.eq(known, nickname))
+        .map(|(_, thread_id)| *thread_id);
+    if let Some(thread_id) = known {
+        return Ok(ChannelId(thread_id));
+    }
+
+    // Threads created before a restart are still in the cache.
+    let parent_id = ChannelId(config.channel_id);
+    let cached = discord
+        .cache
+        .guild_channel(parent_id)
+        .and_then(|channel| discord.cache.guild(channel.guild_id))
+        .and_then(|guild| {
+            guild.threads.into_iter().find(|thread| {
+                thread.parent_id == Some(parent_id) && casemapping.eq(&thread.name, nickname)
+            })
+        });
+    let thread_id = match cached {
+        Some(thread) => thread.id,
+        None => {
+            info!(
+                "IRC| Creating a thread for private messages of {}",
+                nickname
+            );
+            parent_id
+                .create_private_thread(&discord.http, |thread| {
+                    thread.name(nickname).kind(ChannelType::PrivateThread)
+                })
+                .await?
+                .id
+        }
+    };
+
+    state
+        .private_chats()
+        .threads
+        .insert(nickname.to_string(), thread_id.0);
+    Ok(thread_id)
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use libirc::client::Sender;
+use libirc::client::prelude::Command;
+
+use crate::config::{IrcConfig, NickRecovery};
+use crate::state::State;
+
+/// Time between two checks whether the services freed the nickname after `GHOST` or `RECOVER`.
+const RECOVERY_DELAY: Duration = Duration::from_secs(2);
+/// Number of checks before keeping the alternative nickname.
+const RECOVERY_ATTEMPTS: u32 = 5;
+
+/// Identifies to the nickname service and takes the configured nickname back if the server gave
+/// us another one. Returns whether the channel can be joined right away.
+pub fn identify(irc_sender: &Sender, config: &IrcConfig, state: &Arc<State>) -> Result<bool> {
+    let Some(services) = &config.services else {
+        return Ok(true);
+    };
+    let Some(wanted) = config.connection.nickname.as_deref() else {
+        return Ok(true);
+    };
+    let account = services.account.as_deref().unwrap_or(wanted);
+
+    let (logged_in, current, has_nickname) = {
+        let session = state.irc();
+        (
+            session.logged_in,
+            session.nickname.clone(),
+            session.is_me(wanted),
+        )
+    };
+    if !logged_in {
+        info!("IRC| Identifying to {} as {}", services.nickserv, account);
+        irc_sender.send_privmsg(
+            &services.nickserv,
+            format!("IDENTIFY {} {}", account, services.password),
+        )?;
+    }
+
+    if !has_nickname {
+        if let Some(command) = services.recovery.command() {
+            info!(
+                "IRC| Nickname {} is in use, recovering it with {}",
+                wanted, command
+            );
+            irc_sender.send_privmsg(
+                &services.nickserv,
+                format!("{} {} {}", command, wanted, services.password),
+            )?;
+            // `REGAIN` changes our nickname itself. Otherwise the nickname is only taken once the
+            // services freed it, as the IRC library gives up on the connection when a nickname
+            // change fails and it has no alternative nickname left.
+            if services.recovery != NickRecovery::Regain {
+                wait_for_nickname(irc_sender.clone(), wanted.to_string(), state.clone());
+            }
+        } else {
+            warn!("IRC| Nickname {} is in use, using {:?}", wanted, current);
+        }
+    }
+
+    if logged_in || !services.wait_for_identification {
+        return Ok(true);
+    }
+
+    // Services do not always tell us that the identification succeeded, so join anyway after a
+    // while.
+    let irc_sender = irc_sender.clone();
+    let config = config.clone();
+    let state = state.clone();
+    let timeout = Duration::from_secs(services.identify_timeout);
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if !state.irc().join_sent {
+            warn!("IRC| Not identified after {:?}, joining anyway", timeout);
+            if let Err(e) = super::channel::join(&irc_sender, &config, &state) {
+                error!("IRC| Failed to join the channel: {}", e);
+            }
+        }
+    });
+    Ok(false)
+}
+
+/// Asks the server with `ISON` whether the nickname is free, until we have it or give up.
+fn wait_for_nickname(irc_sender: Sender, wanted: String, state: Arc<State>) {
+    tokio::spawn(async move {
+        for _ in 0..RECOVERY_ATTEMPTS {
+            tokio::time::sleep(RECOVERY_DELAY).await;
+            if state.irc().is_me(&wanted) {
+                return;
+            }
+            if let Err(e) = irc_sender.send(Command::ISON(vec![wanted.clone()])) {
+                error!("IRC| Failed to check the nickname {}: {}", wanted, e);
+                return;
+            }
+        }
+        if !state.irc().is_me(&wanted) {
+            warn!(
+                "IRC| Nickname {} was not freed, keeping the current one",
+                wanted
+            );
+        }
+    });
+}
+
+/// Takes the configured nickname back when a RPL_ISON reply tells that nobody uses it.
+pub fn ison(irc_sender: &Sender, config: &IrcConfig, state: &State, args: &[String]) -> Result<()> {
+    let Some(services) = &config.services else {
+        return Ok(());
+    };
+    let Some(wanted) = config.connection.nickname.as_deref() else {
+        return Ok(());
+    };
+    if matches!(services.recovery, NickRecovery::None | NickRecovery::Regain) {
+        return Ok(());
+    }
+    let session = state.irc();
+    let casemapping = session.casemapping;
+    let online = args.last().is_some_and(|nicknames| {
+        nicknames
+            .split_whitespace()
+            .any(|n| casemapping.eq(n, wanted))
+    });
+    if !online && !session.is_me(wanted) {
+        info!("IRC| Nickname {} is free, taking it back", wanted);
+        irc_sender.send(Command::NICK(wanted.to_string()))?;
+    }
+    Ok(())
+}
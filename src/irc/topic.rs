@@ -0,0 +1,41 @@
+use anyhow::Result;
+use serenity::model::id::ChannelId;
+
+use crate::config::TopicConfig;
+use crate::state::State;
+
+/// Called when `nickname` sets the IRC topic to `topic`. Copies the topic to Discord, unless
+/// Discord already has it.
+pub async fn changed(
+    discord: &serenity::CacheAndHttp,
+    config: &TopicConfig,
+    state: &State,
+    channel_id: u64,
+    nickname: &str,
+    topic: String,
+) -> Result<()> {
+    {
+        let mut topics = state.topics();
+        topics.irc = Some(topic.clone());
+        if !config.direction.to_discord() || topics.discord.as_ref() == Some(&topic) {
+            return Ok(());
+        }
+        topics.discord = Some(topic.clone());
+    }
+    info!("IRC> {} changed the topic to: {}", nickname, topic);
+
+    let channel_id = ChannelId(channel_id);
+    if let Err(e) = channel_id.edit(&discord.http, |c| c.topic(&topic)).await {
+        warn!("Failed to set the Discord topic: {}", e);
+        return Ok(());
+    }
+    if config.announce {
+        let message = if topic.is_empty() {
+            format!("**{}** cleared the topic on IRC.", nickname)
+        } else {
+            format!("**{}** changed the topic on IRC to: {}", nickname, topic)
+        };
+        channel_id.say(&discord.http, message).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use libirc::client::prelude::Response;
+
+use crate::state::State;
+
+/// Collects a WHOIS reply for the Discord user who asked for it.
+pub fn reply(state: &State, response: Response, args: &[String]) {
+    let Some(nickname) = args.get(1) else {
+        return;
+    };
+    let line = match (response, &args[2..]) {
+        (Response::RPL_WHOISUSER, [user, host, _, realname, ..]) => {
+            format!("**{}** is `{}@{}` ({})", nickname, user, host, realname)
+        }
+        (Response::RPL_WHOISSERVER, [server, info, ..]) => {
+            format!("Connected to `{}` ({})", server, info)
+        }
+        (Response::RPL_WHOISOPERATOR, _) => "Is an IRC operator".to_string(),
+        (Response::RPL_AWAY, [message, ..]) => format!("Away: {}", message),
+        (Response::RPL_WHOISIDLE, [idle, signon, ..]) => {
+            let idle: u64 = idle.parse().unwrap_or_default();
+            // Discord renders timestamps in the time zone of the reader.
+            format!(
+                "Idle for {}m {}s, connected <t:{}:R>",
+                idle / 60,
+                idle % 60,
+                signon
+            )
+        }
+        (Response::RPL_WHOISCHANNELS, [channels, ..]) => format!("Channels: {}", channels),
+        (Response::RPL_ENDOFWHOIS, _) => return state.finish_whois(nickname),
+        (Response::ERR_NOSUCHNICK, _) => {
+            state.push_whois(nickname, format!("There is no **{}** on IRC.", nickname));
+            return state.finish_whois(nickname);
+        }
+        _ => return,
+    };
+    state.push_whois(nickname, line);
+}
+
+/// Collects a RPL_WHOISACCOUNT reply, which the IRC library does not know.
+pub fn account(state: &State, args: &[String]) {
+    if let [_, nickname, account, ..] = args {
+        state.push_whois(nickname, format!("Logged in as `{}`", account));
+    }
+}
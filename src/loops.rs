@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::casemapping::CaseMapping;
+use crate::config::{LoopDetectionConfig, default_relay_pattern};
+use crate::store::Origin;
+
+/// Number of relay prefixes stripped from a line, for messages relayed by several bridges.
+const MAX_RELAYS: usize = 3;
+
+/// Author of a message, to recognize the other bridges.
+#[derive(Debug, Clone, Copy)]
+pub enum Author<'a> {
+    Irc(&'a str, CaseMapping),
+    /// Discord user or webhook ID.
+    Discord(u64),
+}
+
+#[derive(Debug)]
+struct Bridge {
+    name: String,
+    irc_nicknames: Vec<String>,
+    discord_ids: Vec<u64>,
+    pattern: Regex,
+}
+
+impl Bridge {
+    fn is_author(&self, author: Author) -> bool {
+        match author {
+            Author::Irc(nickname, casemapping) => self
+                .irc_nicknames
+                .iter()
+                .any(|bridge| casemapping.eq(bridge, nickname)),
+            Author::Discord(id) => self.discord_ids.contains(&id),
+        }
+    }
+}
+
+/// Recognizes messages which the bridge relayed and which other bridges sent back, so that they
+/// do not go around forever.
+#[derive(Debug)]
+pub struct LoopDetector {
+    window: Duration,
+    bridges: Vec<Bridge>,
+    /// Pattern of lines relayed by unknown bridges, like `<nick> text`.
+    pattern: Regex,
+    /// Recently relayed messages with the side they were sent on.
+    relayed: Mutex<VecDeque<(Origin, String, Instant)>>,
+}
+
+impl LoopDetector {
+    pub fn new(config: &LoopDetectionConfig) -> Result<Self> {
+        let bridges = config
+            .bridges
+            .iter()
+            .map(|bridge| {
+                Ok(Bridge {
+                    name: bridge.name.clone(),
+                    irc_nicknames: bridge.irc_nicknames.clone(),
+                    discord_ids: bridge.discord_ids.clone(),
+                    pattern: Regex::new(&bridge.pattern)
+                        .with_context(|| format!("Invalid pattern of bridge {}", bridge.name))?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(LoopDetector {
+            window: Duration::from_secs(config.window),
+            bridges,
+            pattern: Regex::new(&default_relay_pattern())?,
+            relayed: Default::default(),
+        })
+    }
+
+    /// Remembers a message from the `origin` side which was relayed to the other side.
+    pub fn record(&self, origin: Origin, text: &str) {
+        let text = normalize(text);
+        if text.is_empty() {
+            return;
+        }
+        let mut relayed = self.relayed.lock().unwrap_or_else(|e| e.into_inner());
+        relayed.retain(|(_, _, relayed_at)| relayed_at.elapsed() < self.window);
+        relayed.push_back((origin, text, Instant::now()));
+    }
+
+    /// Returns the name of the bridge which sent a message back to the `origin` side, if the
+    /// message is the echo of one relayed from there.
+    ///
+    /// Messages of known bridges are echoes when they are a relayed message, or when a line
+    /// relayed by them is. Lines relayed by unknown bridges, like `<nick> text`, are echoes when
+    /// their text is.
+    pub fn echo(&self, origin: Origin, author: Author, text: &str) -> Option<&str> {
+        let bridge = self.bridges.iter().find(|bridge| bridge.is_author(author));
+        let text = strip_formatting(text);
+        let mut candidates = Vec::new();
+        if bridge.is_some() {
+            candidates.push(normalize(&text));
+        }
+        let patterns = bridge
+            .map(|bridge| &bridge.pattern)
+            .into_iter()
+            .chain(std::iter::repeat_n(&self.pattern, MAX_RELAYS));
+        let mut text = text.as_str();
+        for pattern in patterns {
+            if let Some(stripped) = pattern.captures(text).and_then(|c| c.name("text")) {
+                text = stripped.as_str();
+                candidates.push(normalize(text));
+            }
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let mut relayed = self.relayed.lock().unwrap_or_else(|e| e.into_inner());
+        relayed.retain(|(_, _, relayed_at)| relayed_at.elapsed() < self.window);
+        relayed
+            .iter()
+            .any(|(o, relayed, _)| *o == origin && candidates.contains(relayed))
+            .then(|| bridge.map_or("an unknown bridge", |bridge| bridge.name.as_str()))
+    }
+}
+
+/// Removes IRC formatting codes and Markdown markers, which bridges convert between, and the
+/// backslashes escaping Markdown.
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x02' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f' => {}
+            '*' | '_' | '~' | '`' | '|' | '\\' => {}
+            // Colors, like `\x0304` or `\x0304,12`.
+            '\x03' => {
+                for _ in 0..2 {
+                    chars.next_if(char::is_ascii_digit);
+                }
+                if chars.peek() == Some(&',') {
+                    let mut rest = chars.clone();
+                    rest.next();
+                    if rest.peek().is_some_and(char::is_ascii_digit) {
+                        chars = rest;
+                        for _ in 0..2 {
+                            chars.next_if(char::is_ascii_digit);
+                        }
+                    }
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+fn normalize(text: &str) -> String {
+    strip_formatting(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn test_loop_detector() -> Result<()> {
+    use crate::config::BridgeConfig;
+
+    let detector = LoopDetector::new(&LoopDetectionConfig {
+        window: 30,
+        bridges: vec![BridgeConfig {
+            name: "matrix".to_string(),
+            irc_nicknames: vec!["MatrixBot".to_string()],
+            discord_ids: vec![42],
+            pattern: r"^\[m\] (?P<text>.+)$".to_string(),
+        }],
+    })?;
+    detector.record(Origin::Discord, "deploy  done");
+    detector.record(Origin::Irc, "hello");
+    detector.record(Origin::Irc, "\x02ship\x02 it \x0304,12now");
+
+    let irc = |nickname| Author::Irc(nickname, CaseMapping::Rfc1459);
+    // Discord messages relayed to IRC are only echoes when they come back to Discord.
+    assert_eq!(
+        detector.echo(Origin::Discord, Author::Discord(42), "deploy done"),
+        Some("matrix")
+    );
+    assert_eq!(
+        detector.echo(Origin::Irc, irc("matrixbot"), "deploy done"),
+        None
+    );
+    assert_eq!(
+        detector.echo(Origin::Irc, irc("matrixbot"), "[m] <bridge> <alice> hello"),
+        Some("matrix")
+    );
+    assert_eq!(
+        detector.echo(Origin::Irc, irc("carol"), "<alice> hello"),
+        Some("an unknown bridge")
+    );
+    // Formatting is converted by the bridges.
+    assert_eq!(
+        detector.echo(Origin::Irc, irc("carol"), r"<alice\> \*\*ship\*\* it now"),
+        Some("an unknown bridge")
+    );
+    assert_eq!(
+        detector.echo(Origin::Irc, Author::Discord(42), "**ship** it now"),
+        Some("matrix")
+    );
+    // Only lines relayed by known bridges may be echoes as is.
+    assert_eq!(detector.echo(Origin::Irc, irc("carol"), "hello"), None);
+    Ok(())
+}
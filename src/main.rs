@@ -1,10 +1,18 @@
 #[macro_use]
 extern crate tracing;
 
+mod admin;
+mod casemapping;
 mod config;
 mod discord;
 mod format;
+mod ignore;
 mod irc;
+mod loops;
+mod roster;
+mod settings;
+mod state;
+mod store;
 mod utils;
 
 use std::env::args;
@@ -22,6 +30,7 @@ async fn irc_handler_future(
     discord_http: Arc<serenity::CacheAndHttp>,
     irc_config: config::IrcConfig,
     discord_config: config::DiscordConfig,
+    state: Arc<state::State>,
     stopper: Option<Stopper>,
 ) -> Result<()> {
     let irc_sender = irc_client.sender();
@@ -33,8 +42,17 @@ async fn irc_handler_future(
             let discord_http = discord_http.clone();
             let irc_config = irc_config.clone();
             let discord_config = discord_config.clone();
+            let state = state.clone();
             async move {
-                irc::handle_irc(msg, irc_sender, &discord_http, irc_config, discord_config).await
+                irc::handle_irc(
+                    msg,
+                    irc_sender,
+                    &discord_http,
+                    irc_config,
+                    discord_config,
+                    &state,
+                )
+                .await
             }
         })
         .map(|res| {
@@ -47,7 +65,7 @@ async fn irc_handler_future(
         })
         .collect::<()>()
         .await;
-    Ok(())
+    bail!("The IRC connection was closed")
 }
 
 #[tokio::main]
@@ -60,6 +78,9 @@ async fn main() -> Result<()> {
     }
     let config::Config {
         exit_on_send_error,
+        store: store_config,
+        ignore_rules,
+        loop_detection,
         irc: irc_config,
         discord: discord_config,
     } = config::Config::from_path(&args[1])?;
@@ -72,19 +93,45 @@ async fn main() -> Result<()> {
 
     let irc_client = Client::from_config(irc_config.connection.clone()).await?;
     let irc_sender = irc_client.sender();
-    irc_client.identify()?;
+    irc::identify(&irc_client, &irc_config)?;
+
+    let store = store::Store::open(store_config.path.as_deref(), store_config.retention)?;
+    let settings = match &store_config.state_file {
+        Some(path) => settings::Settings::load(path)?,
+        None => None,
+    }
+    .unwrap_or_else(|| settings::Settings::from_config(&irc_config, &discord_config));
+    let ignore_rules = ignore::IgnoreRules::new(ignore_rules)?;
+    let loops = loop_detection
+        .as_ref()
+        .map(loops::LoopDetector::new)
+        .transpose()?;
+    let state = Arc::new(state::State::new(
+        store,
+        ignore_rules,
+        loops,
+        settings,
+        store_config.state_file,
+    ));
 
     let mut intents =
         GatewayIntents::MESSAGE_CONTENT | GatewayIntents::GUILDS | GatewayIntents::GUILD_MESSAGES;
-    if irc_config.auto_detect_avatar {
+    if irc_config.auto_detect_avatar || discord_config.names.is_some() {
         intents |= GatewayIntents::GUILD_MEMBERS | GatewayIntents::GUILD_PRESENCES;
     }
+    if discord_config.reactions.is_some() {
+        intents |= GatewayIntents::GUILD_MESSAGE_REACTIONS;
+    }
+    if discord_config.typing.is_some() {
+        intents |= GatewayIntents::GUILD_MESSAGE_TYPING;
+    }
 
     let mut discord_client = serenity::Client::builder(discord_config.token.clone(), intents)
         .event_handler(discord::DiscordHandler::new(
             discord_config.clone(),
             irc_config.clone(),
             irc_sender,
+            state.clone(),
             stopper.clone(),
         ))
         .intents(intents)
@@ -95,6 +142,7 @@ async fn main() -> Result<()> {
         discord_client.cache_and_http.clone(),
         irc_config,
         discord_config,
+        state,
         stopper.clone(),
     );
 
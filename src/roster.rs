@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::casemapping::CaseMapping;
+
+/// Members of the bridged IRC channel with their privileges, built from NAMES and WHO replies and
+/// updated on JOIN, PART, KICK, QUIT, NICK, MODE and AWAY.
+#[derive(Debug)]
+pub struct Roster {
+    /// Privilege modes with their prefix, from the highest, as in the `PREFIX` token of
+    /// RPL_ISUPPORT.
+    prefixes: Vec<(char, char)>,
+    casemapping: CaseMapping,
+    /// Members by the lowercase form of their nickname.
+    members: HashMap<String, Member>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub nickname: String,
+    /// Privilege modes of the member, from the highest.
+    pub modes: String,
+    pub away: bool,
+}
+
+impl Member {
+    fn new(nickname: &str) -> Self {
+        Member {
+            nickname: nickname.to_string(),
+            modes: String::new(),
+            away: false,
+        }
+    }
+}
+
+impl Default for Roster {
+    fn default() -> Self {
+        Roster {
+            prefixes: vec![('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')],
+            casemapping: CaseMapping::default(),
+            members: HashMap::new(),
+        }
+    }
+}
+
+impl Roster {
+    fn key(&self, nickname: &str) -> String {
+        self.casemapping.lower(nickname)
+    }
+
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+        self.members = self
+            .members
+            .drain()
+            .map(|(_, member)| (casemapping.lower(&member.nickname), member))
+            .collect();
+    }
+
+    /// Reads the privileges of the server from a `PREFIX` token like `(ohv)@%+`.
+    pub fn set_prefixes(&mut self, token: &str) {
+        let Some((modes, prefixes)) = token
+            .strip_prefix('(')
+            .and_then(|token| token.split_once(')'))
+        else {
+            return;
+        };
+        self.prefixes = modes.chars().zip(prefixes.chars()).collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.members.clear();
+    }
+
+    /// Adds the members of a RPL_NAMREPLY, like `@alice +bob carol`.
+    pub fn add_names(&mut self, names: &str) {
+        for name in names.split_whitespace() {
+            let nickname = name.trim_start_matches(|c| self.prefixes.iter().any(|p| p.1 == c));
+            let modes: String = name[..name.len() - nickname.len()]
+                .chars()
+                .filter_map(|c| self.prefixes.iter().find(|p| p.1 == c).map(|p| p.0))
+                .collect();
+            self.join(nickname);
+            for mode in modes.chars() {
+                self.set_mode(nickname, mode, true);
+            }
+        }
+    }
+
+    pub fn join(&mut self, nickname: &str) {
+        self.members
+            .insert(self.key(nickname), Member::new(nickname));
+    }
+
+    /// Removes a member, returning whether they were in the channel.
+    pub fn part(&mut self, nickname: &str) -> bool {
+        self.members.remove(&self.key(nickname)).is_some()
+    }
+
+    pub fn rename(&mut self, old: &str, new: &str) {
+        if let Some(mut member) = self.members.remove(&self.key(old)) {
+            member.nickname = new.to_string();
+            self.members.insert(self.key(new), member);
+        }
+    }
+
+    pub fn set_away(&mut self, nickname: &str, away: bool) {
+        if let Some(member) = self.members.get_mut(&self.key(nickname)) {
+            member.away = away;
+        }
+    }
+
+    pub fn contains(&self, nickname: &str) -> bool {
+        self.members.contains_key(&self.key(nickname))
+    }
+
+    pub fn is_privilege(&self, mode: char) -> bool {
+        self.prefixes.iter().any(|p| p.0 == mode)
+    }
+
+    /// Gives or takes a privilege mode of a member.
+    pub fn set_mode(&mut self, nickname: &str, mode: char, enabled: bool) {
+        if !self.is_privilege(mode) {
+            return;
+        }
+        let Some(member) = self.members.get_mut(&self.key(nickname)) else {
+            return;
+        };
+        let mut modes: Vec<_> = member.modes.chars().filter(|m| *m != mode).collect();
+        if enabled {
+            modes.push(mode);
+        }
+        modes.sort_by_key(|m| self.prefixes.iter().position(|p| p.0 == *m));
+        member.modes = modes.into_iter().collect();
+    }
+
+    /// Returns the prefix of the highest privilege of a member, like `@`.
+    pub fn prefix(&self, nickname: &str) -> Option<char> {
+        self.prefix_of(self.members.get(&self.key(nickname))?)
+    }
+
+    pub fn prefix_of(&self, member: &Member) -> Option<char> {
+        let mode = member.modes.chars().next()?;
+        self.prefixes.iter().find(|p| p.0 == mode).map(|p| p.1)
+    }
+
+    /// Returns the members sorted by privilege, then by nickname.
+    pub fn members(&self) -> Vec<&Member> {
+        let rank = |member: &Member| {
+            member
+                .modes
+                .chars()
+                .next()
+                .and_then(|mode| self.prefixes.iter().position(|p| p.0 == mode))
+                .unwrap_or(self.prefixes.len())
+        };
+        let mut members: Vec<_> = self.members.values().collect();
+        members.sort_by_cached_key(|member| (rank(member), self.key(&member.nickname)));
+        members
+    }
+}
+
+#[test]
+fn test_roster() {
+    let mut roster = Roster::default();
+    roster.set_prefixes("(ohv)@%+");
+    roster.add_names("@alice +bob @+carol dave");
+    assert_eq!(roster.prefix("alice"), Some('@'));
+    assert_eq!(roster.prefix("Bob"), Some('+'));
+    assert_eq!(roster.prefix("carol"), Some('@'));
+    assert_eq!(roster.prefix("dave"), None);
+
+    roster.set_mode("carol", 'o', false);
+    assert_eq!(roster.prefix("carol"), Some('+'));
+    roster.set_mode("dave", 'h', true);
+    roster.set_mode("dave", 'b', true);
+    assert_eq!(roster.prefix("dave"), Some('%'));
+
+    roster.rename("dave", "erin");
+    assert_eq!(roster.prefix("erin"), Some('%'));
+    let nicknames: Vec<_> = roster.members().iter().map(|m| &m.nickname).collect();
+    assert_eq!(nicknames, ["alice", "erin", "bob", "carol"]);
+
+    roster.part("erin");
+    roster.join("erin");
+    assert_eq!(roster.prefix("erin"), None);
+
+    roster.add_names("+[bot]");
+    assert_eq!(roster.prefix("{BOT}"), Some('+'));
+    roster.set_casemapping(CaseMapping::Ascii);
+    assert_eq!(roster.prefix("{BOT}"), None);
+    assert_eq!(roster.prefix("[BOT]"), Some('+'));
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DiscordConfig, IrcConfig};
+
+/// Settings which admins change at runtime. They start from the configuration file, and are saved
+/// to the state file which then takes precedence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// IRC nicknames whose messages are not relayed.
+    pub irc_ignores: Vec<String>,
+    /// Discord names whose messages are not relayed.
+    pub discord_ignores: Vec<String>,
+    pub bridge_member_changes: bool,
+    /// Whether relaying messages is paused in both directions.
+    pub paused: bool,
+}
+
+impl Settings {
+    pub fn from_config(irc: &IrcConfig, discord: &DiscordConfig) -> Self {
+        Settings {
+            irc_ignores: irc.ignores.clone(),
+            discord_ignores: discord.ignores.clone(),
+            bridge_member_changes: irc.bridge_member_changes,
+            paused: false,
+        }
+    }
+
+    /// Loads the settings saved in the state file, if there is one.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(toml::from_str(&content)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        // Write the whole file at once, so a crash cannot leave half of it.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, toml::to_string(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_settings() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("discord-irc-test-{}.toml", std::process::id()));
+    assert_eq!(Settings::load(&path)?, None);
+
+    let settings = Settings {
+        irc_ignores: vec!["spambot".to_string()],
+        paused: true,
+        ..Default::default()
+    };
+    settings.save(&path)?;
+    assert_eq!(Settings::load(&path)?, Some(settings));
+    fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,429 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use libirc::client::prelude::Command;
+use tokio::sync::oneshot;
+
+use crate::casemapping::CaseMapping;
+use crate::ignore::IgnoreRules;
+use crate::loops::LoopDetector;
+use crate::roster::Roster;
+use crate::settings::Settings;
+use crate::store::{MessageRecord, Store};
+
+/// How long we wait for the server to accept or reject a relayed line.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of relayed private messages remembered for replies.
+const PRIVATE_MESSAGE_HISTORY: usize = 1000;
+
+/// Runtime state shared by the IRC and the Discord handlers.
+#[derive(Debug)]
+pub struct State {
+    pub store: Store,
+    pub ignore_rules: IgnoreRules,
+    pub loops: Option<LoopDetector>,
+    settings: Mutex<Settings>,
+    /// File where changes of the settings are saved.
+    state_file: Option<PathBuf>,
+    irc: Mutex<IrcSession>,
+    deliveries: Mutex<VecDeque<PendingDelivery>>,
+    private_chats: Mutex<PrivateChats>,
+    reactions: Mutex<HashMap<u64, PendingReactions>>,
+    typing: Mutex<TypingTimes>,
+    refusal_notices: Mutex<HashMap<u64, Instant>>,
+    thread_channels: Mutex<HashMap<u64, String>>,
+    /// Lines waiting for the bot to join the IRC channel of a thread, by lowercase channel name.
+    joining: Mutex<HashMap<String, Vec<Command>>>,
+    topics: Mutex<Topics>,
+    roster: Mutex<Roster>,
+    whois: Mutex<HashMap<String, PendingWhois>>,
+}
+
+impl State {
+    pub fn new(
+        store: Store,
+        ignore_rules: IgnoreRules,
+        loops: Option<LoopDetector>,
+        settings: Settings,
+        state_file: Option<PathBuf>,
+    ) -> Self {
+        State {
+            store,
+            ignore_rules,
+            loops,
+            settings: Mutex::new(settings),
+            state_file,
+            irc: Default::default(),
+            deliveries: Default::default(),
+            private_chats: Default::default(),
+            reactions: Default::default(),
+            typing: Default::default(),
+            refusal_notices: Default::default(),
+            thread_channels: Default::default(),
+            joining: Default::default(),
+            topics: Default::default(),
+            roster: Default::default(),
+            whois: Default::default(),
+        }
+    }
+
+    pub fn settings(&self) -> MutexGuard<'_, Settings> {
+        self.settings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Changes the settings, saving them to the state file.
+    pub fn update_settings(&self, f: impl FnOnce(&mut Settings)) -> Result<()> {
+        let mut settings = self.settings();
+        f(&mut settings);
+        if let Some(path) = &self.state_file {
+            settings.save(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn irc(&self) -> MutexGuard<'_, IrcSession> {
+        self.irc.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns whether messages and member changes of an IRC user are ignored by nickname.
+    pub fn is_irc_ignored(&self, nickname: &str) -> bool {
+        let casemapping = self.irc().casemapping;
+        self.settings()
+            .irc_ignores
+            .iter()
+            .any(|ignored| casemapping.eq(ignored, nickname))
+    }
+
+    pub fn push_delivery(&self, delivery: PendingDelivery) {
+        let mut deliveries = self.deliveries.lock().unwrap_or_else(|e| e.into_inner());
+        deliveries.retain(|d| d.sent_at.elapsed() < DELIVERY_TIMEOUT);
+        deliveries.push_back(delivery);
+    }
+
+    /// Counts a line the bot sends to `irc_channel` on its own, like an edit or a notice, so that
+    /// the answer of the server to it is not taken for the answer to a relayed message.
+    pub fn push_bot_line(&self, irc_channel: &str) {
+        if !self.irc().enabled_caps.contains("echo-message") {
+            return;
+        }
+        self.push_delivery(PendingDelivery {
+            irc_channel: irc_channel.to_string(),
+            message: None,
+            lines: 1,
+            irc_msgid: None,
+            refusal: None,
+            sent_at: Instant::now(),
+        });
+    }
+
+    /// Marks the oldest line sent to `irc_channel` which the server has not answered yet as
+    /// delivered with `irc_msgid`. The server answers in order, so this is the line an echo
+    /// refers to. Returns the delivery once all of its lines are answered.
+    pub fn line_delivered(
+        &self,
+        irc_channel: &str,
+        irc_msgid: Option<&str>,
+    ) -> Option<PendingDelivery> {
+        self.answer_line(irc_channel, |delivery| {
+            if delivery.irc_msgid.is_none() {
+                delivery.irc_msgid = irc_msgid.map(Into::into);
+            }
+        })
+    }
+
+    /// Marks the oldest line sent to `irc_channel` which the server has not answered yet as
+    /// refused for `reason`. Returns the delivery once all of its lines are answered.
+    pub fn line_refused(&self, irc_channel: &str, reason: &str) -> Option<PendingDelivery> {
+        self.answer_line(irc_channel, |delivery| {
+            delivery.refusal.get_or_insert_with(|| reason.to_string());
+        })
+    }
+
+    fn answer_line(
+        &self,
+        irc_channel: &str,
+        f: impl FnOnce(&mut PendingDelivery),
+    ) -> Option<PendingDelivery> {
+        let mut deliveries = self.deliveries.lock().unwrap_or_else(|e| e.into_inner());
+        deliveries.retain(|d| d.sent_at.elapsed() < DELIVERY_TIMEOUT);
+        let idx = deliveries
+            .iter()
+            .position(|d| d.irc_channel.eq_ignore_ascii_case(irc_channel))?;
+        let delivery = &mut deliveries[idx];
+        f(delivery);
+        delivery.lines -= 1;
+        if delivery.lines > 0 {
+            return None;
+        }
+        deliveries.remove(idx)
+    }
+
+    pub fn private_chats(&self) -> MutexGuard<'_, PrivateChats> {
+        self.private_chats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns whether typing may be shown on IRC, if it was not in the last `interval`.
+    pub fn typing_on_irc(&self, interval: Duration) -> bool {
+        let mut typing = self.typing.lock().unwrap_or_else(|e| e.into_inner());
+        throttle(&mut typing.irc, interval)
+    }
+
+    /// Returns whether typing may be shown on Discord, if it was not in the last `interval`.
+    pub fn typing_on_discord(&self, interval: Duration) -> bool {
+        let mut typing = self.typing.lock().unwrap_or_else(|e| e.into_inner());
+        throttle(&mut typing.discord, interval)
+    }
+
+    /// Returns whether a Discord user not allowed to be relayed may be told so, if they were not
+    /// in the last `interval`.
+    pub fn refusal_noticed(&self, user_id: u64, interval: Duration) -> bool {
+        let mut notices = self
+            .refusal_notices
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        notices.retain(|_, noticed_at| noticed_at.elapsed() < interval);
+        if notices.contains_key(&user_id) {
+            return false;
+        }
+        notices.insert(user_id, Instant::now());
+        true
+    }
+
+    /// IRC channels of Discord threads, by thread ID.
+    pub fn thread_channels(&self) -> MutexGuard<'_, HashMap<u64, String>> {
+        self.thread_channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Holds the lines sent to `irc_channel` until the server confirms the bot joined it, as
+    /// servers refuse messages from outside of most channels.
+    pub fn start_joining(&self, irc_channel: &str) {
+        self.joining()
+            .insert(irc_channel.to_ascii_lowercase(), Vec::new());
+    }
+
+    /// Queues `command` if it is sent to a channel the bot is joining. Otherwise returns it, to be
+    /// sent right away.
+    pub fn queue_until_joined(&self, irc_channel: &str, command: Command) -> Option<Command> {
+        match self.joining().get_mut(&irc_channel.to_ascii_lowercase()) {
+            Some(queue) => {
+                queue.push(command);
+                None
+            }
+            None => Some(command),
+        }
+    }
+
+    /// Returns the lines queued for `irc_channel` once the bot joined it, or failed to.
+    pub fn finish_joining(&self, irc_channel: &str) -> Vec<Command> {
+        self.joining()
+            .remove(&irc_channel.to_ascii_lowercase())
+            .unwrap_or_default()
+    }
+
+    fn joining(&self) -> MutexGuard<'_, HashMap<String, Vec<Command>>> {
+        self.joining.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the Discord thread bridged to `irc_channel`.
+    pub fn thread_of_channel(&self, irc_channel: &str) -> Option<u64> {
+        self.thread_channels()
+            .iter()
+            .find(|(_, channel)| channel.eq_ignore_ascii_case(irc_channel))
+            .map(|(thread_id, _)| *thread_id)
+    }
+
+    pub fn topics(&self) -> MutexGuard<'_, Topics> {
+        self.topics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Members of the bridged IRC channel.
+    pub fn roster(&self) -> MutexGuard<'_, Roster> {
+        self.roster.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts collecting the WHOIS replies about `nickname`, which are sent to the returned
+    /// receiver at the end of the replies.
+    pub fn start_whois(&self, nickname: &str) -> oneshot::Receiver<Vec<String>> {
+        let (done, receiver) = oneshot::channel();
+        let key = self.irc().casemapping.lower(nickname);
+        let mut whois = self.whois.lock().unwrap_or_else(|e| e.into_inner());
+        whois.insert(
+            key,
+            PendingWhois {
+                lines: Vec::new(),
+                done,
+            },
+        );
+        receiver
+    }
+
+    /// Adds a line to the WHOIS replies about `nickname`, if someone asked for them.
+    pub fn push_whois(&self, nickname: &str, line: String) {
+        let key = self.irc().casemapping.lower(nickname);
+        let mut whois = self.whois.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pending) = whois.get_mut(&key) {
+            pending.lines.push(line);
+        }
+    }
+
+    pub fn finish_whois(&self, nickname: &str) {
+        let key = self.irc().casemapping.lower(nickname);
+        let mut whois = self.whois.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pending) = whois.remove(&key) {
+            let _ = pending.done.send(pending.lines);
+        }
+    }
+
+    /// Reactions waiting to be announced on IRC, by Discord message ID.
+    pub fn reactions(&self) -> MutexGuard<'_, HashMap<u64, PendingReactions>> {
+        self.reactions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn throttle(last: &mut Option<Instant>, interval: Duration) -> bool {
+    if last.is_some_and(|last| last.elapsed() < interval) {
+        return false;
+    }
+    *last = Some(Instant::now());
+    true
+}
+
+/// The lines relayed from a Discord message, waiting for the IRC server to accept or reject them.
+#[derive(Debug, Clone)]
+pub struct PendingDelivery {
+    pub irc_channel: String,
+    /// Channel and ID of the Discord message, or `None` for a line the bot sends on its own.
+    pub message: Option<(u64, u64)>,
+    /// Number of lines the server has not answered yet.
+    pub lines: usize,
+    /// ID the server gave to the first line.
+    pub irc_msgid: Option<String>,
+    /// Why the server refused the first refused line.
+    pub refusal: Option<String>,
+    pub sent_at: Instant,
+}
+
+/// WHOIS replies collected for a Discord command.
+#[derive(Debug)]
+pub struct PendingWhois {
+    lines: Vec<String>,
+    done: oneshot::Sender<Vec<String>>,
+}
+
+/// Last known topic of each side. A change to the topic the other side already has is the echo
+/// of our own change, and is not synchronized back.
+#[derive(Debug, Default)]
+pub struct Topics {
+    pub irc: Option<String>,
+    pub discord: Option<String>,
+}
+
+/// When typing was last shown on each side of the bridge.
+#[derive(Debug, Default)]
+struct TypingTimes {
+    irc: Option<Instant>,
+    discord: Option<Instant>,
+}
+
+/// Reactions to a bridged message, collected to be announced on IRC in a single line.
+#[derive(Debug)]
+pub struct PendingReactions {
+    pub original: MessageRecord,
+    /// Users who reacted with every emoji, in the order the emojis were first used.
+    pub reactions: Vec<(String, Vec<String>)>,
+}
+
+impl PendingReactions {
+    pub fn new(original: MessageRecord) -> Self {
+        PendingReactions {
+            original,
+            reactions: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, emoji: String, user: String) {
+        match self.reactions.iter_mut().find(|(e, _)| *e == emoji) {
+            Some((_, users)) if users.contains(&user) => {}
+            Some((_, users)) => users.push(user),
+            None => self.reactions.push((emoji, vec![user])),
+        }
+    }
+}
+
+/// What we learned about the current IRC connection.
+#[derive(Debug, Default)]
+pub struct IrcSession {
+    /// Capabilities advertised by the server with `CAP LS`.
+    pub available_caps: HashSet<String>,
+    /// Capabilities acknowledged by the server with `CAP ACK`.
+    pub enabled_caps: HashSet<String>,
+    pub sasl: SaslStatus,
+    /// Nickname the server registered us with, which may differ from the configured one.
+    pub nickname: Option<String>,
+    /// How the server compares nicknames.
+    pub casemapping: CaseMapping,
+    /// Whether the server told us that we are logged in to an account.
+    pub logged_in: bool,
+    /// Whether the bot has already asked to join the channel.
+    pub join_sent: bool,
+    /// Whether the bot is currently in the channel.
+    pub joined: bool,
+    /// Number of rejoin attempts made in quick succession, used for the backoff.
+    pub rejoin_attempts: u32,
+    pub last_rejoin: Option<Instant>,
+}
+
+impl IrcSession {
+    /// Returns whether `nickname` is the current nickname of the bot.
+    pub fn is_me(&self, nickname: &str) -> bool {
+        self.nickname
+            .as_deref()
+            .is_some_and(|me| self.casemapping.eq(me, nickname))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum SaslStatus {
+    #[default]
+    NotStarted,
+    InProgress,
+    Succeeded,
+    Failed(String),
+}
+
+/// Where private messages of IRC users were relayed to on Discord.
+#[derive(Debug, Default)]
+pub struct PrivateChats {
+    /// Discord thread of every IRC user, when private messages are relayed into threads.
+    pub threads: HashMap<String, u64>,
+    /// Recently relayed Discord messages with the IRC user who sent them.
+    messages: VecDeque<(u64, String)>,
+}
+
+impl PrivateChats {
+    pub fn nickname_of_thread(&self, thread_id: u64) -> Option<&str> {
+        self.threads
+            .iter()
+            .find(|(_, id)| **id == thread_id)
+            .map(|(nickname, _)| nickname.as_str())
+    }
+
+    pub fn push_message(&mut self, message_id: u64, nickname: String) {
+        if self.messages.len() >= PRIVATE_MESSAGE_HISTORY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((message_id, nickname));
+    }
+
+    pub fn nickname_of_message(&self, message_id: u64) -> Option<&str> {
+        self.messages
+            .iter()
+            .find(|(id, _)| *id == message_id)
+            .map(|(_, nickname)| nickname.as_str())
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+/// Which side of the bridge a message was originally sent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Discord,
+    Irc,
+}
+
+impl Origin {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Origin::Discord => "discord",
+            Origin::Irc => "irc",
+        }
+    }
+}
+
+/// A bridged message, identified by its Discord message ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRecord {
+    pub discord_id: u64,
+    /// The `msgid` tag of the message on IRC, if the server supports it.
+    pub irc_msgid: Option<String>,
+    pub origin: Origin,
+    /// Discord user ID for messages from Discord.
+    pub author_id: Option<u64>,
+    /// Discord display name or IRC nickname of the author.
+    pub author: String,
+    pub content: String,
+    /// Unix timestamp of when the message was bridged.
+    pub timestamp: i64,
+}
+
+impl MessageRecord {
+    /// Seconds elapsed since the message was bridged.
+    pub fn age(&self) -> u64 {
+        (now() - self.timestamp).max(0) as u64
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let origin: String = row.get("origin")?;
+        Ok(MessageRecord {
+            discord_id: row.get::<_, i64>("discord_id")? as u64,
+            irc_msgid: row.get("irc_msgid")?,
+            origin: if origin == "irc" {
+                Origin::Irc
+            } else {
+                Origin::Discord
+            },
+            author_id: row.get::<_, Option<i64>>("author_id")?.map(|id| id as u64),
+            author: row.get("author")?,
+            content: row.get("content")?,
+            timestamp: row.get("timestamp")?,
+        })
+    }
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// SQLite database mapping Discord message IDs to IRC messages, keeping them for the retention
+/// period.
+#[derive(Debug)]
+pub struct Store {
+    conn: Mutex<Connection>,
+    retention: u64,
+}
+
+impl Store {
+    pub fn open(path: Option<&Path>, retention: u64) -> Result<Self> {
+        let conn = match path {
+            Some(path) => Connection::open(path)?,
+            None => Connection::open_in_memory()?,
+        };
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                discord_id INTEGER PRIMARY KEY,
+                irc_msgid TEXT,
+                origin TEXT NOT NULL,
+                author_id INTEGER,
+                author TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS messages_irc_msgid ON messages (irc_msgid);
+            CREATE INDEX IF NOT EXISTS messages_author ON messages (origin, author, timestamp);
+            CREATE INDEX IF NOT EXISTS messages_timestamp ON messages (timestamp);
+            CREATE TABLE IF NOT EXISTS forum_posts (
+                number INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_id INTEGER NOT NULL UNIQUE
+            );",
+        )?;
+        Ok(Store {
+            conn: Mutex::new(conn),
+            retention,
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a bridged message, dropping the messages older than the retention period.
+    pub fn insert(&self, record: &MessageRecord) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "DELETE FROM messages WHERE timestamp < ?1",
+            params![now() - self.retention as i64],
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO messages
+                (discord_id, irc_msgid, origin, author_id, author, content, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.discord_id as i64,
+                record.irc_msgid,
+                record.origin.as_str(),
+                record.author_id.map(|id| id as i64),
+                record.author,
+                record.content,
+                record.timestamp,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the number IRC users refer to a forum post with, giving one to the post if it has
+    /// none. Numbers are short, unlike thread IDs, and are not given again.
+    pub fn forum_post_number(&self, thread_id: u64) -> Result<u64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT OR IGNORE INTO forum_posts (thread_id) VALUES (?1)",
+            params![thread_id as i64],
+        )?;
+        let number: i64 = conn.query_row(
+            "SELECT number FROM forum_posts WHERE thread_id = ?1",
+            params![thread_id as i64],
+            |row| row.get(0),
+        )?;
+        Ok(number as u64)
+    }
+
+    /// Returns the thread of the forum post with `number`.
+    pub fn forum_post(&self, number: u64) -> Result<Option<u64>> {
+        let thread_id: Option<i64> = self
+            .conn()
+            .query_row(
+                "SELECT thread_id FROM forum_posts WHERE number = ?1",
+                params![number as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(thread_id.map(|id| id as u64))
+    }
+
+    pub fn by_discord_id(&self, discord_id: u64) -> Result<Option<MessageRecord>> {
+        let record = self
+            .conn()
+            .query_row(
+                "SELECT * FROM messages WHERE discord_id = ?1",
+                params![discord_id as i64],
+                MessageRecord::from_row,
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    pub fn by_irc_msgid(&self, irc_msgid: &str) -> Result<Option<MessageRecord>> {
+        let record = self
+            .conn()
+            .query_row(
+                "SELECT * FROM messages WHERE irc_msgid = ?1",
+                params![irc_msgid],
+                MessageRecord::from_row,
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    /// Returns the last message `author` sent on the `origin` side.
+    pub fn last_from(&self, origin: Origin, author: &str) -> Result<Option<MessageRecord>> {
+        let record = self
+            .conn()
+            .query_row(
+                "SELECT * FROM messages WHERE origin = ?1 AND author = ?2
+                    ORDER BY timestamp DESC, rowid DESC LIMIT 1",
+                params![origin.as_str(), author],
+                MessageRecord::from_row,
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    pub fn set_content(&self, discord_id: u64, content: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE messages SET content = ?2 WHERE discord_id = ?1",
+            params![discord_id as i64, content],
+        )?;
+        Ok(())
+    }
+
+    /// Sets the IRC ID of a message, if it has none. Multi-line messages keep the ID of the first
+    /// line.
+    pub fn set_irc_msgid(&self, discord_id: u64, irc_msgid: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE messages SET irc_msgid = ?2 WHERE discord_id = ?1 AND irc_msgid IS NULL",
+            params![discord_id as i64, irc_msgid],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove(&self, discord_id: u64) -> Result<Option<MessageRecord>> {
+        let record = self.by_discord_id(discord_id)?;
+        self.conn().execute(
+            "DELETE FROM messages WHERE discord_id = ?1",
+            params![discord_id as i64],
+        )?;
+        Ok(record)
+    }
+}
+
+#[test]
+fn test_store() -> Result<()> {
+    let store = Store::open(None, 60)?;
+    let record = MessageRecord {
+        discord_id: 1,
+        irc_msgid: None,
+        origin: Origin::Irc,
+        author_id: None,
+        author: "alice".to_string(),
+        content: "teh cat".to_string(),
+        timestamp: now(),
+    };
+    store.insert(&record)?;
+    store.insert(&MessageRecord {
+        discord_id: 2,
+        content: "old".to_string(),
+        timestamp: now() - 120,
+        ..record.clone()
+    })?;
+    store.set_content(1, "the cat")?;
+    store.set_irc_msgid(1, "abc")?;
+
+    let found = store.last_from(Origin::Irc, "alice")?.unwrap();
+    assert_eq!(found.content, "the cat");
+    assert_eq!(found.irc_msgid.as_deref(), Some("abc"));
+    assert_eq!(store.last_from(Origin::Discord, "alice")?, None);
+    assert_eq!(store.by_irc_msgid("abc")?, Some(found));
+
+    // Expired messages are dropped on the next insertion.
+    store.insert(&MessageRecord {
+        discord_id: 3,
+        ..record
+    })?;
+    assert_eq!(store.by_discord_id(2)?, None);
+    assert!(store.remove(3)?.is_some());
+    assert_eq!(store.by_discord_id(3)?, None);
+
+    assert_eq!(store.forum_post_number(100)?, 1);
+    assert_eq!(store.forum_post_number(200)?, 2);
+    assert_eq!(store.forum_post_number(100)?, 1);
+    assert_eq!(store.forum_post(2)?, Some(200));
+    assert_eq!(store.forum_post(3)?, None);
+    Ok(())
+}
@@ -37,6 +37,55 @@ pub fn insert_zero_width_spaces_into_nickname(nick: &str) -> String {
     }
 }
 
+/// Matches `text` against an IRC mask like `*!*@example.com`, where `*` matches any characters and
+/// `?` one character, ignoring ASCII case.
+pub fn mask_matches(mask: &str, text: &str) -> bool {
+    let mask: Vec<_> = mask.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let text: Vec<_> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut m, mut t) = (0, 0);
+    // Position of the last `*` in the mask, and of the text it was tried at.
+    let mut star = None;
+    while t < text.len() {
+        if m < mask.len() && (mask[m] == '?' || mask[m] == text[t]) {
+            m += 1;
+            t += 1;
+        } else if m < mask.len() && mask[m] == '*' {
+            star = Some((m, t));
+            m += 1;
+        } else if let Some((star_m, star_t)) = star {
+            m = star_m + 1;
+            t = star_t + 1;
+            star = Some((star_m, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    mask[m..].iter().all(|c| *c == '*')
+}
+
+/// Shortens `text` to its first line and at most `max` characters, ending with `…` if anything
+/// was cut.
+pub fn excerpt(text: &str, max: usize) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    let graphemes: Vec<_> = line.graphemes(true).collect();
+    if graphemes.len() <= max && line.len() == text.trim().len() {
+        line.to_string()
+    } else {
+        let s: String = graphemes[..graphemes.len().min(max)].concat();
+        format!("{}…", s.trim_end())
+    }
+}
+
+#[test]
+pub fn test_excerpt() {
+    assert_eq!(excerpt("deploy done", 40), "deploy done");
+    assert_eq!(excerpt("deploy done", 6), "deploy…");
+    assert_eq!(excerpt("deploy done", 7), "deploy…");
+    assert_eq!(excerpt("first\nsecond", 40), "first…");
+    assert_eq!(excerpt("배포 완료", 2), "배포…");
+    assert_eq!(excerpt("", 40), "");
+}
+
 #[test]
 pub fn test_insert_boms_into_nickname() {
     let f = insert_zero_width_spaces_into_nickname;
@@ -52,3 +101,13 @@ pub fn test_insert_boms_into_nickname() {
 
     assert_eq!(f("a̐éö̲"), "a̐\u{200B}é\u{200B}ö̲");
 }
+
+#[test]
+pub fn test_mask_matches() {
+    assert!(mask_matches("*!*@example.com", "alice!~a@Example.com"));
+    assert!(mask_matches("alice!*", "Alice!~a@host"));
+    assert!(mask_matches("a?ice*", "alice"));
+    assert!(!mask_matches("*!*@example.com", "alice!~a@example.org"));
+    assert!(!mask_matches("bob!*", "alice!~a@host"));
+    assert!(mask_matches("*", ""));
+}